@@ -2177,6 +2177,14 @@ impl<'a, 'tcx> BuilderMethods<'a, 'tcx> for Builder<'a, 'tcx> {
                 );
             }
             result
+        } else if !self.codegen_args.bounds_checks
+            && self.panic_bounds_check_fn_id.get() == Some(callee_val)
+        {
+            // With bounds checks disabled, failing one is undefined behavior, which leaves the
+            // optimizer free to drop the branch to it (and the check itself) altogether.
+            self.unreachable();
+            *self = self.build_sibling_block("unreachable_continue");
+            self.undef(result_type)
         } else if [self.panic_fn_id.get(), self.panic_bounds_check_fn_id.get()]
             .contains(&Some(callee_val))
         {
@@ -316,6 +316,14 @@ impl<'cx, 'tcx> Builder<'cx, 'tcx> {
                 SpirvType::AccelerationStructureKhr.def(self.span(), self)
             }
             Op::TypeRayQueryKHR => SpirvType::RayQueryKhr.def(self.span(), self),
+            // Imports are deduplicated when linking, so every `asm!` block can import what it uses.
+            Op::ExtInstImport => self
+                .emit_global()
+                .ext_inst_import(inst.operands[0].unwrap_literal_string()),
+            // Debug strings go in their own section, e.g. for `debug_printf!`.
+            Op::String => self
+                .emit_global()
+                .string(inst.operands[0].unwrap_literal_string()),
             Op::Variable => {
                 // OpVariable with Function storage class should be emitted inside the function,
                 // however, all other OpVariables should appear in the global scope instead.
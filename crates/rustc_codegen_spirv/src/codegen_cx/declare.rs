@@ -112,11 +112,19 @@ impl<'tcx> CodegenCx<'tcx> {
 
         let attrs = AggregatedSpirvAttributes::parse(self, self.tcx.get_attrs(instance.def_id()));
         if let Some(entry) = attrs.entry.map(|attr| attr.value) {
+            // A name given in the attribute takes precedence over one the build exports it as.
             let entry_name = entry
                 .name
                 .as_ref()
                 .map(ToString::to_string)
-                .unwrap_or_else(|| instance.to_string());
+                .unwrap_or_else(|| {
+                    let default = instance.to_string();
+                    self.codegen_args
+                        .entry_point_names
+                        .iter()
+                        .find(|(from, _)| *from == default)
+                        .map_or(default, |(_, name)| name.clone())
+                });
             self.entry_stub(&instance, &fn_abi, declared, entry_name, entry)
         }
         if attrs.unroll_loops.is_some() {
@@ -278,6 +278,12 @@ pub struct CodegenArgs {
     pub disassemble_globals: bool,
 
     pub name_variables: bool,
+    /// Whether out of bounds indexing aborts, rather than being undefined behavior.
+    pub bounds_checks: bool,
+    /// Whether to record how long each function takes to codegen, and each entry point to link.
+    pub time_functions: bool,
+    /// Entry points to export under a different name, as `(default name, exported name)` pairs.
+    pub entry_point_names: Vec<(String, String)>,
 
     // spirv-val flags
     pub relax_struct_store: bool,
@@ -321,6 +327,26 @@ impl CodegenArgs {
             "Keep OpName for OpVariables, strip all others.",
             "",
         );
+        opts.optflagopt(
+            "",
+            "no-bounds-checks",
+            "Treat failed bounds checks as unreachable, instead of aborting.",
+            "",
+        );
+        opts.optflagopt(
+            "",
+            "time-functions",
+            "Record how long each function takes to codegen, and each entry point to link.",
+            "",
+        );
+        opts.optmulti(
+            "",
+            "entry-point-name",
+            "Export the entry point that would be named DEFAULT as NAME instead. DEFAULT is the path \
+             of its function within the shader crate, without the crate's name, e.g. `main` or \
+             `shaders::main_fs`.",
+            "DEFAULT=NAME",
+        );
 
         opts.optflagopt("", "relax-struct-store", "Allow store from one struct type to a different type with compatible layout and members.", "");
         opts.optflagopt("", "relax-logical-pointer", "Allow allocating an object of a pointer type and returning a pointer value from a function in logical addressing mode", "");
@@ -338,6 +364,19 @@ impl CodegenArgs {
         let disassemble_globals = matches.opt_present("disassemble-globals");
 
         let name_variables = matches.opt_present("name-variables");
+        let bounds_checks = !matches.opt_present("no-bounds-checks");
+        let time_functions = matches.opt_present("time-functions");
+        let entry_point_names = matches
+            .opt_strs("entry-point-name")
+            .into_iter()
+            .map(|arg| match arg.split_once('=') {
+                Some((default, name)) => Ok((default.to_string(), name.to_string())),
+                None => Err(getopts::Fail::UnrecognizedOption(format!(
+                    "--entry-point-name={} (expected DEFAULT=NAME)",
+                    arg
+                ))),
+            })
+            .collect::<Result<_, _>>()?;
 
         let relax_struct_store = matches.opt_present("relax-struct-store");
         let relax_logical_pointer = matches.opt_present("relax-logical-pointer");
@@ -356,6 +395,9 @@ impl CodegenArgs {
             disassemble_globals,
 
             name_variables,
+            bounds_checks,
+            time_functions,
+            entry_point_names,
 
             relax_struct_store,
             relax_logical_pointer,
@@ -683,3 +725,49 @@ impl<'tcx> AsmMethods for CodegenCx<'tcx> {
         todo!()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::CodegenArgs;
+
+    fn parse(args: &[&str]) -> Result<CodegenArgs, rustc_session::getopts::Fail> {
+        CodegenArgs::parse(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>())
+    }
+
+    fn names(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|&(from, to)| (from.to_string(), to.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn entry_point_names() {
+        let args = parse(&[
+            "--entry-point-name=main=hello_world",
+            "--entry-point-name",
+            "main_vs=vertex",
+        ])
+        .unwrap();
+        assert_eq!(
+            args.entry_point_names,
+            names(&[("main", "hello_world"), ("main_vs", "vertex")])
+        );
+    }
+
+    #[test]
+    fn entry_point_name_splits_at_first_equals() {
+        let args = parse(&["--entry-point-name=main=a=b"]).unwrap();
+        assert_eq!(args.entry_point_names, names(&[("main", "a=b")]));
+    }
+
+    #[test]
+    fn entry_point_name_without_equals() {
+        assert!(parse(&["--entry-point-name=main"]).is_err());
+    }
+
+    #[test]
+    fn no_entry_point_names() {
+        assert!(parse(&[]).unwrap().entry_point_names.is_empty());
+    }
+}
@@ -2,6 +2,7 @@ use rustc_data_structures::fx::FxHashMap;
 use serde::{Deserialize, Serialize};
 use std::fmt::Write;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(untagged)]
@@ -34,9 +35,106 @@ impl ModuleResult {
 pub struct CompileResult {
     pub module: ModuleResult,
     pub entry_points: Vec<String>,
+    /// The capabilities declared by the module (or any of the modules), by name.
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+    /// The target the module was built for, e.g. `spirv-unknown-vulkan1.1`.
+    #[serde(default)]
+    pub target: String,
+    /// Extra flags passed to `rustc` via `SpirvBuilder::rustc_flag`. Filled in by `spirv-builder`,
+    /// as the backend can't tell them apart from the rest of the command line.
+    #[serde(default)]
+    pub rustc_flags: Vec<String>,
+    /// The `--target-dir` the shader crate was built in, if `spirv-builder` picked one rather
+    /// than leaving it to cargo. Filled in by `spirv-builder`, like `rustc_flags`.
+    #[serde(default)]
+    pub target_dir: Option<PathBuf>,
+    /// The number of codegen units the shader crate was split into, if set with
+    /// `SpirvBuilder::codegen_units` rather than left to the profile. Filled in by
+    /// `spirv-builder`, like `rustc_flags`.
+    #[serde(default)]
+    pub codegen_units: Option<u32>,
+    /// Whether out of bounds indexing aborts the invocation, or is undefined behavior, as set by
+    /// `SpirvBuilder::bounds_checks`.
+    #[serde(default = "default_bounds_checks")]
+    pub bounds_checks: bool,
+    /// With `SpirvBuilder::time_entry_points`, how long each function of the shader crate took to
+    /// codegen, and with `multimodule`, each entry point's module to optimize and validate.
+    /// Slowest first.
+    #[serde(default)]
+    pub timings: Vec<Timing>,
+}
+
+/// Which part of the build a [`Timing`] measures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimingStage {
+    /// Generating SPIR-V for one function, not including the functions it calls. Only functions
+    /// of the shader crate itself are timed, and only when they're actually codegenned, i.e. not
+    /// when incremental compilation reuses them.
+    Codegen,
+    /// Optimizing and validating the module of one entry point, after it's been split out.
+    Link,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Timing {
+    /// The function's path, or the entry point's name.
+    pub name: String,
+    pub stage: TimingStage,
+    pub duration: Duration,
+}
+
+impl Timing {
+    /// Sorts `timings` slowest first, as [`CompileResult::timings`] lists them.
+    pub fn sort(timings: &mut [Timing]) {
+        timings.sort_by(|a, b| b.duration.cmp(&a.duration));
+    }
+}
+
+fn default_bounds_checks() -> bool {
+    true
+}
+
+/// What a build produced, as plain data for build scripts to generate host code from, e.g.
+/// constants for the entry point names.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShaderMetadata {
+    pub entry_points: Vec<String>,
+    pub capabilities: Vec<String>,
+    pub target: String,
 }
 
 impl CompileResult {
+    pub fn metadata(&self) -> ShaderMetadata {
+        ShaderMetadata {
+            entry_points: self.entry_points.clone(),
+            capabilities: self.capabilities.clone(),
+            target: self.target.clone(),
+        }
+    }
+
+    /// Formats [`Self::timings`] as a table, slowest first.
+    pub fn timings_table(&self) -> String {
+        let mut table = String::new();
+        writeln!(table, "{:>10}  {:<7}  name", "ms", "stage").unwrap();
+        for timing in &self.timings {
+            let stage = match timing.stage {
+                TimingStage::Codegen => "codegen",
+                TimingStage::Link => "link",
+            };
+            writeln!(
+                table,
+                "{:>10.3}  {:<7}  {}",
+                timing.duration.as_secs_f64() * 1000.0,
+                stage,
+                timing.name
+            )
+            .unwrap();
+        }
+        table
+    }
+
     pub fn codegen_entry_point_strings(&self) -> String {
         let trie = Trie::create_from(self.entry_points.iter().map(|x| x as &str));
         let mut builder = String::new();
@@ -166,4 +264,84 @@ pub mod y {
 "#,
         );
     }
+
+    #[test]
+    fn missing_fields_default() {
+        // Results written before the fields were added still load, with bounds checks on.
+        let result: CompileResult =
+            serde_json::from_str(r#"{"module":"module","entry_points":["main_cs"]}"#).unwrap();
+        assert!(result.bounds_checks);
+        assert!(result.timings.is_empty());
+    }
+
+    #[test]
+    fn metadata() {
+        let result: CompileResult = serde_json::from_value(serde_json::json!({
+            "module": "module",
+            "entry_points": ["main_cs"],
+            "capabilities": ["Shader"],
+            "target": "spirv-unknown-vulkan1.1"
+        }))
+        .unwrap();
+        assert_eq!(
+            result.metadata(),
+            ShaderMetadata {
+                entry_points: vec!["main_cs".to_string()],
+                capabilities: vec!["Shader".to_string()],
+                target: "spirv-unknown-vulkan1.1".to_string(),
+            }
+        );
+    }
+
+    fn timing(name: &str, stage: TimingStage, micros: u64) -> Timing {
+        Timing {
+            name: name.to_string(),
+            stage,
+            duration: Duration::from_micros(micros),
+        }
+    }
+
+    fn timed_result(timings: Vec<Timing>) -> CompileResult {
+        let mut result: CompileResult =
+            serde_json::from_str(r#"{"module":"module","entry_points":["main_cs"]}"#).unwrap();
+        result.timings = timings;
+        result
+    }
+
+    #[test]
+    fn timings_sorted_slowest_first() {
+        let mut timings = vec![
+            timing("a", TimingStage::Codegen, 10),
+            timing("b", TimingStage::Link, 30),
+            timing("c", TimingStage::Codegen, 20),
+            timing("d", TimingStage::Codegen, 30),
+        ];
+        Timing::sort(&mut timings);
+        let names: Vec<_> = timings.iter().map(|timing| &timing.name[..]).collect();
+        // Ties keep the order they were recorded in.
+        assert_eq!(names, ["b", "d", "c", "a"]);
+    }
+
+    #[test]
+    fn timings_table() {
+        let result = timed_result(vec![
+            timing("main_cs", TimingStage::Link, 12_345_678),
+            timing("shader::main_cs", TimingStage::Codegen, 1_500),
+        ]);
+        assert_eq!(
+            result.timings_table(),
+            r#"        ms  stage    name
+ 12345.678  link     main_cs
+     1.500  codegen  shader::main_cs
+"#,
+        );
+    }
+
+    #[test]
+    fn empty_timings_table() {
+        assert_eq!(
+            timed_result(Vec::new()).timings_table(),
+            "        ms  stage    name\n"
+        );
+    }
 }
@@ -168,7 +168,8 @@ use std::fs::{create_dir_all, File};
 use std::io::Cursor;
 use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 fn dump_mir<'tcx>(
     tcx: TyCtxt<'tcx>,
@@ -264,8 +265,12 @@ impl MetadataLoader for SpirvMetadataLoader {
     }
 }
 
-#[derive(Clone)]
-struct SpirvCodegenBackend;
+#[derive(Clone, Default)]
+struct SpirvCodegenBackend {
+    /// How long each function took to codegen, with `--time-functions`. Codegen units are
+    /// compiled in parallel, and the results are only needed again at link time.
+    codegen_timings: Arc<Mutex<Vec<Timing>>>,
+}
 
 impl CodegenBackend for SpirvCodegenBackend {
     fn target_features(&self, sess: &Session) -> Vec<Symbol> {
@@ -378,6 +383,8 @@ impl CodegenBackend for SpirvCodegenBackend {
         let codegen_args = CodegenArgs::from_session(sess);
         let emit_multiple_modules = codegen_args.module_output_type == ModuleOutputType::Multiple;
 
+        let codegen_timings = std::mem::take(&mut *self.codegen_timings.lock().unwrap());
+
         let timer = sess.timer("link_crate");
         link::link(
             sess,
@@ -386,6 +393,7 @@ impl CodegenBackend for SpirvCodegenBackend {
             &codegen_results.crate_name.as_str(),
             legalize,
             emit_multiple_modules,
+            &codegen_timings,
         );
         drop(timer);
 
@@ -533,6 +541,8 @@ impl ExtraBackendMethods for SpirvCodegenBackend {
         let cgu = tcx.codegen_unit(cgu_name);
 
         let cx = CodegenCx::new(tcx, cgu);
+        let time_functions = CodegenArgs::from_session(tcx.sess).time_functions;
+        let mut timings = Vec::new();
         let do_codegen = || {
             let mono_items = cx.codegen_unit.items_in_deterministic_order(cx.tcx);
 
@@ -558,7 +568,15 @@ impl ExtraBackendMethods for SpirvCodegenBackend {
                         continue;
                     }
                 }
+                let start = time_functions.then(Instant::now);
                 mono_item.define::<Builder<'_, '_>>(&cx);
+                if let (Some(start), MonoItem::Fn(instance)) = (start, mono_item) {
+                    timings.push(Timing {
+                        name: instance.to_string(),
+                        stage: TimingStage::Codegen,
+                        duration: start.elapsed(),
+                    });
+                }
             }
 
             if let Some(_entry) = maybe_create_entry_wrapper::<Builder<'_, '_>>(&cx) {
@@ -572,6 +590,7 @@ impl ExtraBackendMethods for SpirvCodegenBackend {
         } else {
             with_no_trimmed_paths(do_codegen);
         }
+        self.codegen_timings.lock().unwrap().extend(timings);
         let spirv_module = cx.finalize_module().assemble();
 
         (
@@ -633,5 +652,5 @@ pub fn __rustc_codegen_backend() -> Box<dyn CodegenBackend> {
         eprintln!("note: `rust-gpu` version {}\n", env!("CARGO_PKG_VERSION"));
     }));
 
-    Box::new(SpirvCodegenBackend)
+    Box::new(SpirvCodegenBackend::default())
 }
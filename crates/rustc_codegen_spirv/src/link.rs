@@ -1,5 +1,6 @@
 use crate::{
     linker, CompileResult, ModuleResult, SpirvCodegenBackend, SpirvModuleBuffer, SpirvThinBuffer,
+    Timing, TimingStage,
 };
 use rustc_codegen_ssa::back::lto::{LtoModuleCodegen, SerializedModule, ThinModule, ThinShared};
 use rustc_codegen_ssa::back::write::CodegenContext;
@@ -22,6 +23,7 @@ use std::fs::File;
 use std::io::{BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Instant;
 use tar::{Archive, Builder, Header};
 
 pub fn link<'a>(
@@ -31,6 +33,7 @@ pub fn link<'a>(
     crate_name: &str,
     legalize: bool,
     emit_multiple_modules: bool,
+    codegen_timings: &[Timing],
 ) {
     let output_metadata = sess.opts.output_types.contains_key(&OutputType::Metadata);
     for &crate_type in sess.crate_types().iter() {
@@ -70,6 +73,7 @@ pub fn link<'a>(
                     codegen_results,
                     legalize,
                     emit_multiple_modules,
+                    codegen_timings,
                 ),
                 other => sess.err(&format!("CrateType {:?} not supported yet", other)),
             }
@@ -119,6 +123,7 @@ fn link_exe(
     codegen_results: &CodegenResults,
     legalize: bool,
     emit_multiple_modules: bool,
+    codegen_timings: &[Timing],
 ) {
     let mut objects = Vec::new();
     let mut rlibs = Vec::new();
@@ -155,8 +160,14 @@ fn link_exe(
         std::fs::create_dir_all(&out_dir).unwrap();
     }
 
+    let mut timings = if cg_args.time_functions {
+        codegen_timings.to_vec()
+    } else {
+        Vec::new()
+    };
+
     use rspirv::binary::Assemble;
-    let compile_result = match spv_binary {
+    let mut compile_result = match spv_binary {
         linker::LinkResult::SingleModule(spv_binary) => {
             let mut module_filename = out_dir;
             module_filename.push("module");
@@ -166,24 +177,52 @@ fn link_exe(
             CompileResult {
                 module: module_result,
                 entry_points: entry_points(&spv_binary),
+                capabilities: capabilities(&spv_binary),
+                target: sess.opts.target_triple.to_string(),
+                rustc_flags: Vec::new(),
+                target_dir: None,
+                codegen_units: None,
+                bounds_checks: cg_args.bounds_checks,
+                timings: Vec::new(),
             }
         }
         linker::LinkResult::MultipleModules(map) => {
             let mut hashmap = FxHashMap::default();
             let entry_points = map.keys().cloned().collect();
+            let mut all_capabilities = Vec::new();
             for (name, spv_binary) in map {
+                all_capabilities.extend(capabilities(&spv_binary));
                 let mut module_filename = out_dir.clone();
                 module_filename.push(sanitize_filename::sanitize(&name));
+                let start = Instant::now();
                 post_link_single_module(sess, &cg_args, spv_binary.assemble(), &module_filename);
+                if cg_args.time_functions {
+                    timings.push(Timing {
+                        name: name.clone(),
+                        stage: TimingStage::Link,
+                        duration: start.elapsed(),
+                    });
+                }
                 hashmap.insert(name, module_filename);
             }
             let module_result = ModuleResult::MultiModule(hashmap);
+            all_capabilities.sort();
+            all_capabilities.dedup();
             CompileResult {
                 module: module_result,
                 entry_points,
+                capabilities: all_capabilities,
+                target: sess.opts.target_triple.to_string(),
+                rustc_flags: Vec::new(),
+                target_dir: None,
+                codegen_units: None,
+                bounds_checks: cg_args.bounds_checks,
+                timings: Vec::new(),
             }
         }
     };
+    Timing::sort(&mut timings);
+    compile_result.timings = timings;
 
     let file = File::create(out_filename).unwrap();
     serde_json::to_writer(BufWriter::new(file), &compile_result).unwrap();
@@ -198,6 +237,17 @@ fn entry_points(module: &rspirv::dr::Module) -> Vec<String> {
         .collect()
 }
 
+fn capabilities(module: &rspirv::dr::Module) -> Vec<String> {
+    module
+        .capabilities
+        .iter()
+        .filter_map(|inst| match inst.operands.get(0)? {
+            rspirv::dr::Operand::Capability(capability) => Some(format!("{:?}", capability)),
+            _ => None,
+        })
+        .collect()
+}
+
 fn post_link_single_module(
     sess: &Session,
     cg_args: &crate::codegen_cx::CodegenArgs,
@@ -0,0 +1,107 @@
+//! Generating host-side constants for the entry points and resource bindings of built modules.
+
+use rustc_codegen_spirv::rspirv::dr::{self, Operand};
+use rustc_codegen_spirv::rspirv::spirv::{Decoration, Op, Word};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+/// Generates Rust source with a `const` for the name of every entry point of the modules at
+/// `module_paths`, in an `entry_points` module, and for the binding and set of every resource
+/// they declare, in a `bindings` module.
+///
+/// Resources are named after their `OpName`, and after their set and binding if they have none.
+pub fn generate<'a>(module_paths: impl IntoIterator<Item = &'a Path>) -> Result<String, Error> {
+    let mut entry_points = BTreeMap::new();
+    let mut bindings = BTreeMap::new();
+    for path in module_paths {
+        let module = dr::load_bytes(std::fs::read(path)?).map_err(|err| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Unable to parse {}: {:?}", path.display(), err),
+            )
+        })?;
+        for inst in &module.entry_points {
+            if let Some(Operand::LiteralString(name)) = inst.operands.get(2) {
+                entry_points.insert(identifier(name), name.clone());
+            }
+        }
+
+        let names = module
+            .debug_names
+            .iter()
+            .filter(|inst| inst.class.opcode == Op::Name)
+            .filter_map(
+                |inst| match (inst.operands.get(0)?, inst.operands.get(1)?) {
+                    (Operand::IdRef(id), Operand::LiteralString(name)) => {
+                        Some((*id, name.as_str()))
+                    }
+                    _ => None,
+                },
+            )
+            .collect::<HashMap<Word, &str>>();
+        let mut sets = HashMap::new();
+        let mut binding_numbers = HashMap::new();
+        for inst in &module.annotations {
+            if let (
+                Some(&Operand::IdRef(target)),
+                Some(&Operand::Decoration(decoration)),
+                Some(&Operand::LiteralInt32(value)),
+            ) = (
+                inst.operands.get(0),
+                inst.operands.get(1),
+                inst.operands.get(2),
+            ) {
+                match decoration {
+                    Decoration::DescriptorSet => {
+                        sets.insert(target, value);
+                    }
+                    Decoration::Binding => {
+                        binding_numbers.insert(target, value);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        for (id, &binding) in &binding_numbers {
+            let set = sets.get(id).copied().unwrap_or(0);
+            let name = match names.get(id) {
+                Some(name) => identifier(name),
+                None => format!("SET{}_BINDING{}", set, binding),
+            };
+            bindings.insert(name, (set, binding));
+        }
+    }
+
+    let mut source = String::from("// Generated by spirv-builder, do not edit.\n\n");
+    source.push_str("pub mod entry_points {\n");
+    for (name, entry_point) in &entry_points {
+        writeln!(source, "    pub const {}: &str = {:?};", name, entry_point).unwrap();
+    }
+    source.push_str("}\n\npub mod bindings {\n");
+    for (name, (set, binding)) in &bindings {
+        writeln!(source, "    pub const {}: u32 = {};", name, binding).unwrap();
+        writeln!(source, "    pub const {}_SET: u32 = {};", name, set).unwrap();
+    }
+    source.push_str("}\n");
+    Ok(source)
+}
+
+/// `name` as a `SCREAMING_SNAKE_CASE` identifier, e.g. `main_cs` as `MAIN_CS`.
+fn identifier(name: &str) -> String {
+    let mut identifier = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    if !identifier.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        identifier.insert(0, '_');
+    }
+    identifier
+}
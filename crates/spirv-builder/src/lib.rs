@@ -53,7 +53,9 @@
 // crate-specific exceptions:
 #![allow()]
 
+mod bindings;
 mod depfile;
+mod manifest;
 #[cfg(feature = "watch")]
 mod watch;
 
@@ -62,15 +64,18 @@ use serde::Deserialize;
 use std::collections::HashMap;
 use std::env;
 use std::error::Error;
+use std::ffi::OsString;
 use std::fmt;
 use std::fmt::Write;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, Read};
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
 
+pub use manifest::{Manifest, ShaderArtifact, MANIFEST_VERSION};
 pub use rustc_codegen_spirv::rspirv::spirv::Capability;
-pub use rustc_codegen_spirv::{CompileResult, ModuleResult};
+pub use rustc_codegen_spirv::{CompileResult, ModuleResult, ShaderMetadata, Timing, TimingStage};
 
 #[derive(Debug)]
 #[non_exhaustive]
@@ -81,6 +86,28 @@ pub enum SpirvBuilderError {
     WatchWithPrintMetadata,
     MetadataFileMissing(std::io::Error),
     MetadataFileMalformed(serde_json::Error),
+    VulkanMemoryModelUnsupported(String),
+    /// A capability only Vulkan provides was requested for an OpenGL target.
+    VulkanOnlyCapability {
+        capability: Capability,
+        target: String,
+    },
+    /// Bindless mode needs descriptor indexing, which OpenGL doesn't provide.
+    BindlessUnsupported(String),
+    BuildTimeout(Duration),
+    BindingsFileFailed(std::io::Error),
+    /// The toolchain given to [`SpirvBuilder::toolchain`] isn't installed.
+    ToolchainNotInstalled(String),
+    /// A module the build reported can't be found to check its size.
+    ModuleMissing {
+        module: PathBuf,
+        source: std::io::Error,
+    },
+    ModuleTooLarge {
+        module: PathBuf,
+        size: u64,
+        max_size: u64,
+    },
 }
 
 impl fmt::Display for SpirvBuilderError {
@@ -102,12 +129,75 @@ impl fmt::Display for SpirvBuilderError {
             SpirvBuilderError::MetadataFileMalformed(_) => {
                 f.write_str("Unable to parse multi-module metadata file")
             }
+            SpirvBuilderError::VulkanMemoryModelUnsupported(target) => write!(
+                f,
+                "The Vulkan memory model is only available for Vulkan and WebGPU targets, not {}",
+                target
+            ),
+            SpirvBuilderError::VulkanOnlyCapability { capability, target } => write!(
+                f,
+                "Capability::{:?} is only available for Vulkan targets, not {}",
+                capability, target
+            ),
+            SpirvBuilderError::BindlessUnsupported(target) => write!(
+                f,
+                "Bindless mode needs descriptor indexing, which isn't available for {}",
+                target
+            ),
+            SpirvBuilderError::BuildTimeout(timeout) => {
+                write!(f, "Build took longer than {:?}", timeout)
+            }
+            SpirvBuilderError::BindingsFileFailed(err) => {
+                write!(f, "Unable to generate the bindings file: {}", err)
+            }
+            SpirvBuilderError::ToolchainNotInstalled(toolchain) => write!(
+                f,
+                "The {0} toolchain isn't installed, install it with `rustup toolchain install {0} \
+                 --component rust-src rustc-dev llvm-tools-preview`",
+                toolchain
+            ),
+            SpirvBuilderError::ModuleMissing { module, source } => {
+                write!(f, "Unable to read module {}: {}", module.display(), source)
+            }
+            SpirvBuilderError::ModuleTooLarge {
+                module,
+                size,
+                max_size,
+            } => write!(
+                f,
+                "{} is {} bytes, over the budget of {} bytes",
+                module.display(),
+                size,
+                max_size
+            ),
         }
     }
 }
 
 impl Error for SpirvBuilderError {}
 
+/// Capabilities that come from Vulkan (or its extensions) and have no OpenGL counterpart, so
+/// OpenGL drivers reject modules declaring them. See `docs/src/platform-support.md`.
+const VULKAN_ONLY_CAPABILITIES: &[Capability] = &[
+    Capability::VulkanMemoryModel,
+    Capability::VulkanMemoryModelDeviceScope,
+    Capability::InputAttachment,
+    Capability::InputAttachmentArrayDynamicIndexing,
+    Capability::InputAttachmentArrayNonUniformIndexing,
+    Capability::RuntimeDescriptorArray,
+    Capability::ShaderNonUniform,
+    Capability::UniformBufferArrayNonUniformIndexing,
+    Capability::SampledImageArrayNonUniformIndexing,
+    Capability::StorageBufferArrayNonUniformIndexing,
+    Capability::StorageImageArrayNonUniformIndexing,
+    Capability::UniformTexelBufferArrayNonUniformIndexing,
+    Capability::StorageTexelBufferArrayNonUniformIndexing,
+    Capability::PhysicalStorageBufferAddresses,
+    Capability::RayTracingKHR,
+    Capability::RayQueryKHR,
+    Capability::FragmentShadingRateKHR,
+];
+
 pub enum MemoryModel {
     Simple,
     Vulkan,
@@ -134,8 +224,20 @@ pub struct SpirvBuilder {
     bindless: bool,
     multimodule: bool,
     name_variables: bool,
+    generate_bindings: bool,
+    bounds_checks: bool,
+    time_entry_points: bool,
     capabilities: Vec<Capability>,
     extensions: Vec<String>,
+    rustc_flags: Vec<String>,
+    codegen_units: Option<u32>,
+    entry_point_names: Vec<(String, String)>,
+    shader_crate_features: Vec<String>,
+    build_timeout: Option<Duration>,
+    max_size: Option<u64>,
+    target_dir: Option<PathBuf>,
+    toolchain: Option<String>,
+    print_command: bool,
 
     // spirv-val flags
     pub relax_struct_store: bool,
@@ -156,8 +258,20 @@ impl SpirvBuilder {
             bindless: false,
             multimodule: false,
             name_variables: false,
+            generate_bindings: false,
+            bounds_checks: true,
+            time_entry_points: false,
             capabilities: Vec::new(),
             extensions: Vec::new(),
+            rustc_flags: Vec::new(),
+            codegen_units: None,
+            entry_point_names: Vec::new(),
+            shader_crate_features: Vec::new(),
+            build_timeout: None,
+            max_size: None,
+            target_dir: None,
+            toolchain: None,
+            print_command: false,
 
             relax_struct_store: false,
             relax_logical_pointer: false,
@@ -204,8 +318,49 @@ impl SpirvBuilder {
         self
     }
 
+    /// After building, writes `$OUT_DIR/<crate directory>_bindings.rs` (with `-` replaced by `_`)
+    /// for inclusion with `include!`, with constants for the module's entry point names in
+    /// `entry_points`, and for the binding and set of each resource in `bindings`. For example, a
+    /// `storage` buffer parameter gets `bindings::STORAGE` and `bindings::STORAGE_SET`.
+    ///
+    /// Resources are named after their variables, so this implies [`Self::name_variables`]. As
+    /// `OUT_DIR` is only set for build scripts, this fails anywhere else.
+    pub fn generate_bindings(mut self, v: bool) -> Self {
+        self.generate_bindings = v;
+        self
+    }
+
+    /// Whether indexing out of bounds aborts the invocation, as it does by default. Disabling the
+    /// checks removes their cost from hot loops, but makes out of bounds indexing undefined
+    /// behavior: the shader may read or write arbitrary memory, hang, or crash the driver. Only
+    /// disable them for shaders whose indices are known to be in bounds.
+    pub fn bounds_checks(mut self, v: bool) -> Self {
+        self.bounds_checks = v;
+        self
+    }
+
+    /// Records how long each function of the shader crate takes to codegen and, with
+    /// [`Self::multimodule`], how long each entry point's module takes to optimize and validate,
+    /// in [`CompileResult::timings`], to find the expensive ones in a large shader crate.
+    /// [`CompileResult::timings_table`] formats them for printing.
+    ///
+    /// Functions are timed on their own, not including the functions they call, and functions
+    /// reused by incremental compilation aren't timed at all.
+    pub fn time_entry_points(mut self, v: bool) -> Self {
+        self.time_entry_points = v;
+        self
+    }
+
     /// Adds a capability to the SPIR-V module. Checking if a capability is enabled in code can be
     /// done via `#[cfg(target_feature = "TheCapability")]`.
+    ///
+    /// Vulkan and WebGPU targets always use the Vulkan memory model, declaring
+    /// `Capability::VulkanMemoryModel` (and `SPV_KHR_vulkan_memory_model` before SPIR-V 1.5)
+    /// themselves. Requesting either on any other target is an error.
+    ///
+    /// OpenGL targets (`spirv-unknown-opengl4.x`) reject the capabilities only Vulkan provides,
+    /// such as descriptor indexing, input attachments and ray tracing, with
+    /// [`SpirvBuilderError::VulkanOnlyCapability`].
     pub fn capability(mut self, capability: Capability) -> Self {
         self.capabilities.push(capability);
         self
@@ -218,6 +373,104 @@ impl SpirvBuilder {
         self
     }
 
+    /// Appends a flag to the `rustc` invocation that builds the shader crate, e.g. `--cfg foo` or
+    /// an experimental `-Z` flag. Flags end up in `RUSTFLAGS` as-is, so they can't contain spaces
+    /// other than between arguments, and it's up to the caller to make sure they're valid.
+    pub fn rustc_flag(mut self, flag: impl Into<String>) -> Self {
+        self.rustc_flags.push(flag.into());
+        self
+    }
+
+    /// Splits each crate of the shader build into `units` codegen units, overriding the profile's
+    /// `codegen-units`, and recorded in [`CompileResult::codegen_units`].
+    ///
+    /// More units codegen in parallel, so a large shader crate builds faster, but rustc inlines
+    /// less across them, leaving more for `spirv-opt` to clean up after linking and often a
+    /// somewhat larger module. A single unit gives the most inlining for the slowest build, e.g.
+    /// for release artifacts, while CI that only checks shaders build can afford many.
+    ///
+    /// There's no LTO setting to go with it: the backend links every crate into one module and
+    /// optimizes it as a whole anyway, and doesn't implement rustc's own fat LTO.
+    pub fn codegen_units(mut self, units: u32) -> Self {
+        self.codegen_units = Some(units);
+        self
+    }
+
+    /// Exports the entry point that would be named `default` (the path of its function within the
+    /// shader crate, e.g. `main_fs`) as `name` instead, e.g. `main` for hosts that hardcode that.
+    /// Entry points naming themselves with `entry_point_name` in their attribute keep that name.
+    ///
+    /// The exported name is what ends up in `OpEntryPoint`, and so also in
+    /// [`CompileResult::metadata`] and the generated bindings. Like [`Self::rustc_flag`], both names
+    /// are passed through `RUSTFLAGS`, so they can't contain spaces.
+    pub fn entry_point_name(mut self, default: impl Into<String>, name: impl Into<String>) -> Self {
+        self.entry_point_names.push((default.into(), name.into()));
+        self
+    }
+
+    /// Enables cargo features of the shader crate, e.g. to build several variants of a shader from
+    /// one crate. Unknown features are reported by cargo as a build error.
+    pub fn shader_crate_features(
+        mut self,
+        features: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        self.shader_crate_features
+            .extend(features.into_iter().map(Into::into));
+        self
+    }
+
+    /// Kills the build, failing with [`SpirvBuilderError::BuildTimeout`], if it takes longer than
+    /// `timeout`, e.g. so a shader crate that makes rustc hang can't hang CI along with it. When
+    /// watching, this applies to every rebuild, which then waits for the next change as usual.
+    ///
+    /// Only the `cargo` process is killed, rustc processes it started may take a while to exit.
+    pub fn build_timeout(mut self, timeout: Duration) -> Self {
+        self.build_timeout = Some(timeout);
+        self
+    }
+
+    /// Fails the build with [`SpirvBuilderError::ModuleTooLarge`] if the module (or any of the
+    /// modules, with [`Self::multimodule`]) is larger than `bytes`, to catch shaders outgrowing a
+    /// size budget as they're built.
+    pub fn max_size(mut self, bytes: u64) -> Self {
+        self.max_size = Some(bytes);
+        self
+    }
+
+    /// Builds the shader crate in `path` rather than in a directory picked from `OUT_DIR`. Builds
+    /// sharing a target directory with the host build (or with each other, with different flags)
+    /// wait on each other's locks and invalidate each other's artifacts, so this should be a
+    /// directory only shader builds use.
+    ///
+    /// By default, build scripts build in `target/spirv-builder` next to the host's profile
+    /// directories. Anywhere else, or if `OUT_DIR` isn't laid out as cargo usually does, it's left
+    /// to cargo, as for any other crate.
+    pub fn target_dir(mut self, path: impl AsRef<Path>) -> Self {
+        self.target_dir = Some(path.as_ref().to_owned());
+        self
+    }
+
+    /// Builds the shader crate with the rustup toolchain `toolchain` (e.g. `nightly-2021-05-24`),
+    /// rather than whichever toolchain `cargo` resolves to in the crate's directory. This has to
+    /// be the toolchain `rustc_codegen_spirv` was built with, see its `rust-toolchain` file.
+    ///
+    /// Fails with [`SpirvBuilderError::ToolchainNotInstalled`] if rustup doesn't have it. The
+    /// edition can't be picked the same way: cargo passes the one from the shader crate's
+    /// `Cargo.toml` to rustc itself, and rustc rejects a second one.
+    pub fn toolchain(mut self, toolchain: impl Into<String>) -> Self {
+        self.toolchain = Some(toolchain.into());
+        self
+    }
+
+    /// Prints the `cargo` command that builds the shader crate to stderr before running it, as a
+    /// shell command to reproduce the build by hand: the directory it runs in, the environment
+    /// variables set for it (`RUSTFLAGS`, with the codegen backend and its flags, and
+    /// `RUSTUP_TOOLCHAIN`) and its arguments. When watching, it's printed before every rebuild.
+    pub fn print_command(mut self, v: bool) -> Self {
+        self.print_command = v;
+        self
+    }
+
     /// Allow store from one struct type to a different type with compatible layout and members.
     pub fn relax_struct_store(mut self, v: bool) -> Self {
         self.relax_struct_store = v;
@@ -276,19 +529,108 @@ impl SpirvBuilder {
             MetadataPrintout::None => (),
         }
         let metadata = self.parse_metadata_file(&metadata_file)?;
+        if let Some(max_size) = self.max_size {
+            check_module_sizes(&metadata.module, max_size)?;
+        }
+        if self.generate_bindings {
+            self.write_bindings_file(&metadata)
+                .map_err(SpirvBuilderError::BindingsFileFailed)?;
+        }
 
         Ok(metadata)
     }
 
+    fn write_bindings_file(&self, metadata: &CompileResult) -> std::io::Result<()> {
+        let out_dir = env::var_os("OUT_DIR").ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "OUT_DIR is only set for build scripts",
+            )
+        })?;
+        let source = match &metadata.module {
+            ModuleResult::SingleModule(path) => bindings::generate(Some(path.as_path())),
+            ModuleResult::MultiModule(paths) => {
+                bindings::generate(paths.values().map(PathBuf::as_path))
+            }
+        }?;
+        let crate_name = self
+            .path_to_crate
+            .file_name()
+            .map(|name| name.to_string_lossy().replace('-', "_"))
+            .unwrap_or_default();
+        std::fs::write(
+            Path::new(&out_dir).join(format!("{}_bindings.rs", crate_name)),
+            source,
+        )
+    }
+
+    /// The `--target-dir` to build the shader crate in, see [`Self::target_dir`].
+    pub(crate) fn shader_target_dir(&self) -> Option<PathBuf> {
+        if let Some(dir) = &self.target_dir {
+            return Some(dir.clone());
+        }
+        // If we're nested in `cargo` invocation, use a different `--target-dir`,
+        // to avoid waiting on the same lock (which effectively dead-locks us).
+        // This also helps with e.g. RLS, which uses `--target target/rls`,
+        // so we'll have a separate `target/rls/spirv-builder` for it.
+        let profile = env::var("PROFILE").ok()?;
+        let mut dir = PathBuf::from(env::var_os("OUT_DIR")?);
+        // Strip `$profile/build/*/out`.
+        if dir.ends_with("out")
+            && dir.pop()
+            && dir.pop()
+            && dir.ends_with("build")
+            && dir.pop()
+            && dir.ends_with(profile)
+            && dir.pop()
+        {
+            Some(dir.join("spirv-builder"))
+        } else {
+            None
+        }
+    }
+
     pub(crate) fn validate_running_conditions(&mut self) -> Result<(), SpirvBuilderError> {
         if (self.print_metadata == MetadataPrintout::Full) && self.multimodule {
             return Err(SpirvBuilderError::MultiModuleWithPrintMetadata);
         }
+        let wants_vulkan_memory_model = self.capabilities.contains(&Capability::VulkanMemoryModel)
+            || self
+                .extensions
+                .iter()
+                .any(|ext| ext == "SPV_KHR_vulkan_memory_model");
+        let has_vulkan_memory_model = self.target.starts_with("spirv-unknown-vulkan")
+            || self.target.starts_with("spirv-unknown-webgpu");
+        if wants_vulkan_memory_model && !has_vulkan_memory_model {
+            return Err(SpirvBuilderError::VulkanMemoryModelUnsupported(
+                self.target.clone(),
+            ));
+        }
+        if self.target.starts_with("spirv-unknown-opengl") {
+            if let Some(&capability) = self
+                .capabilities
+                .iter()
+                .find(|capability| VULKAN_ONLY_CAPABILITIES.contains(capability))
+            {
+                return Err(SpirvBuilderError::VulkanOnlyCapability {
+                    capability,
+                    target: self.target.clone(),
+                });
+            }
+            if self.bindless {
+                return Err(SpirvBuilderError::BindlessUnsupported(self.target.clone()));
+            }
+        }
         if !self.path_to_crate.is_dir() {
             return Err(SpirvBuilderError::CratePathDoesntExist(std::mem::take(
                 &mut self.path_to_crate,
             )));
         }
+        if let Some(toolchain) = &self.toolchain {
+            if !toolchain_installed(toolchain) {
+                return Err(SpirvBuilderError::ToolchainNotInstalled(toolchain.clone()));
+            }
+        }
         Ok(())
     }
 
@@ -297,8 +639,12 @@ impl SpirvBuilder {
         at: &Path,
     ) -> Result<CompileResult, SpirvBuilderError> {
         let metadata_contents = File::open(&at).map_err(SpirvBuilderError::MetadataFileMissing)?;
-        let metadata: CompileResult = serde_json::from_reader(BufReader::new(metadata_contents))
-            .map_err(SpirvBuilderError::MetadataFileMalformed)?;
+        let mut metadata: CompileResult =
+            serde_json::from_reader(BufReader::new(metadata_contents))
+                .map_err(SpirvBuilderError::MetadataFileMalformed)?;
+        metadata.rustc_flags = self.rustc_flags.clone();
+        metadata.codegen_units = self.codegen_units;
+        metadata.target_dir = self.shader_target_dir();
         match &metadata.module {
             ModuleResult::SingleModule(spirv_module) => {
                 assert!(!self.multimodule);
@@ -315,6 +661,39 @@ impl SpirvBuilder {
     }
 }
 
+fn check_module_sizes(module: &ModuleResult, max_size: u64) -> Result<(), SpirvBuilderError> {
+    let modules: Vec<&Path> = match module {
+        ModuleResult::SingleModule(path) => vec![path],
+        ModuleResult::MultiModule(paths) => paths.values().map(PathBuf::as_path).collect(),
+    };
+    for module in modules {
+        let size = std::fs::metadata(module)
+            .map_err(|source| SpirvBuilderError::ModuleMissing {
+                module: module.to_owned(),
+                source,
+            })?
+            .len();
+        if size > max_size {
+            return Err(SpirvBuilderError::ModuleTooLarge {
+                module: module.to_owned(),
+                size,
+                max_size,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Whether rustup can run `rustc` from `toolchain`. Without rustup, nothing is installed.
+fn toolchain_installed(toolchain: &str) -> bool {
+    Command::new("rustup")
+        .args(&["run", toolchain, "rustc", "--version"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_or(false, |status| status.success())
+}
+
 // https://github.com/rust-lang/cargo/blob/1857880b5124580c4aeb4e8bc5f1198f491d61b1/src/cargo/util/paths.rs#L29-L52
 fn dylib_path_envvar() -> &'static str {
     if cfg!(windows) {
@@ -363,9 +742,15 @@ fn invoke_rustc(builder: &SpirvBuilder) -> Result<PathBuf, SpirvBuilderError> {
     if builder.multimodule {
         llvm_args.push("--module-output=multiple");
     }
-    if builder.name_variables {
+    if builder.name_variables || builder.generate_bindings {
         llvm_args.push("--name-variables");
     }
+    if !builder.bounds_checks {
+        llvm_args.push("--no-bounds-checks");
+    }
+    if builder.time_entry_points {
+        llvm_args.push("--time-functions");
+    }
     if builder.relax_struct_store {
         llvm_args.push("--relax-struct-store");
     }
@@ -384,6 +769,12 @@ fn invoke_rustc(builder: &SpirvBuilder) -> Result<PathBuf, SpirvBuilderError> {
     if builder.skip_block_layout {
         llvm_args.push("--skip-block-layout");
     }
+    let entry_point_names = builder
+        .entry_point_names
+        .iter()
+        .map(|(default, name)| format!("--entry-point-name={}={}", default, name))
+        .collect::<Vec<_>>();
+    llvm_args.extend(entry_point_names.iter().map(String::as_str));
 
     let llvm_args = if llvm_args.is_empty() {
         String::new()
@@ -415,15 +806,20 @@ fn invoke_rustc(builder: &SpirvBuilder) -> Result<PathBuf, SpirvBuilderError> {
     };
 
     //FIXME: reintroduce v0 mangling, see issue #642
-    let rustflags = format!(
+    let mut rustflags = format!(
         "-Z codegen-backend={} -Zsymbol-mangling-version=legacy{}{}",
         rustc_codegen_spirv.display(),
         feature_flag,
         llvm_args,
     );
+    if let Some(units) = builder.codegen_units {
+        write!(rustflags, " -C codegen-units={}", units).unwrap();
+    }
+    for flag in &builder.rustc_flags {
+        write!(rustflags, " {}", flag).unwrap();
+    }
 
-    let mut cargo = Command::new("cargo");
-    cargo.args(&[
+    let mut args = [
         "build",
         "--lib",
         "--message-format=json-render-diagnostics",
@@ -431,52 +827,136 @@ fn invoke_rustc(builder: &SpirvBuilder) -> Result<PathBuf, SpirvBuilderError> {
         "-Zbuild-std-features=compiler-builtins-mem",
         "--target",
         &*builder.target,
-    ]);
+    ]
+    .iter()
+    .map(OsString::from)
+    .collect::<Vec<_>>();
 
     if builder.release {
-        cargo.arg("--release");
+        args.push("--release".into());
     }
 
-    // If we're nested in `cargo` invocation, use a different `--target-dir`,
-    // to avoid waiting on the same lock (which effectively dead-locks us).
-    // This also helps with e.g. RLS, which uses `--target target/rls`,
-    // so we'll have a separate `target/rls/spirv-builder` for it.
-    if let (Ok(profile), Some(mut dir)) = (
-        env::var("PROFILE"),
-        env::var_os("OUT_DIR").map(PathBuf::from),
-    ) {
-        // Strip `$profile/build/*/out`.
-        if dir.ends_with("out")
-            && dir.pop()
-            && dir.pop()
-            && dir.ends_with("build")
-            && dir.pop()
-            && dir.ends_with(profile)
-            && dir.pop()
-        {
-            cargo.arg("--target-dir").arg(dir.join("spirv-builder"));
-        }
+    if !builder.shader_crate_features.is_empty() {
+        args.push("--features".into());
+        args.push(builder.shader_crate_features.join(",").into());
+    }
+
+    if let Some(target_dir) = builder.shader_target_dir() {
+        args.push("--target-dir".into());
+        args.push(target_dir.into());
+    }
+
+    let mut envs = vec![("RUSTFLAGS", rustflags)];
+    // The rustup proxies pick the toolchain from this before anything else, including the
+    // toolchain an enclosing cargo (e.g. of a build script) was run with.
+    if let Some(toolchain) = &builder.toolchain {
+        envs.push(("RUSTUP_TOOLCHAIN", toolchain.clone()));
+    }
+
+    if builder.print_command {
+        eprintln!("{}", shell_command(&builder.path_to_crate, &envs, &args));
     }
 
-    let build = cargo
+    let mut build = Command::new("cargo")
+        .args(&args)
+        .envs(envs)
+        .stdout(Stdio::piped())
         .stderr(Stdio::inherit())
         .current_dir(&builder.path_to_crate)
-        .env("RUSTFLAGS", rustflags)
-        .output()
+        .spawn()
         .expect("failed to execute cargo build");
 
+    // Read stdout on another thread, so cargo can't block on a full pipe while we wait for it.
+    let mut build_stdout = build.stdout.take().unwrap();
+    let stdout_reader = std::thread::spawn(move || {
+        let mut stdout = String::new();
+        build_stdout.read_to_string(&mut stdout).map(|_| stdout)
+    });
+    let status = match builder.build_timeout {
+        Some(timeout) => match wait_with_timeout(&mut build, timeout) {
+            Ok(status) => status,
+            Err(err) => {
+                // Killing cargo leaves the rustc it spawned running with the pipe open, so the
+                // reader is detached rather than joined, which would wait out the build anyway.
+                drop(stdout_reader);
+                return Err(err);
+            }
+        },
+        None => build.wait().expect("failed to wait for cargo build"),
+    };
+
     // `get_last_artifact` has the side-effect of printing invalid lines, so
     // we do that even in case of an error, to let through any useful messages
     // that ended up on stdout instead of stderr.
-    let stdout = String::from_utf8(build.stdout).unwrap();
+    let stdout = stdout_reader.join().unwrap().unwrap();
     let artifact = get_last_artifact(&stdout);
-    if build.status.success() {
+    if status.success() {
         Ok(artifact.expect("Artifact created when compilation succeeded"))
     } else {
         Err(SpirvBuilderError::BuildFailed)
     }
 }
 
+/// `cargo` with `args`, run in `dir` with `envs`, as a command to paste into a POSIX shell.
+/// Variables that affect the build but come from the caller's environment rather than the
+/// builder, such as an inherited `RUSTUP_TOOLCHAIN`, are listed too, commented out.
+fn shell_command(dir: &Path, envs: &[(&str, String)], args: &[OsString]) -> String {
+    let mut command = format!("cd {} &&", shell_quote(&dir.display().to_string()));
+    for (key, value) in envs {
+        write!(command, " {}={}", key, shell_quote(value)).unwrap();
+    }
+    command.push_str(" cargo");
+    for arg in args {
+        write!(command, " {}", shell_quote(&arg.to_string_lossy())).unwrap();
+    }
+    for key in &[
+        "RUSTUP_TOOLCHAIN",
+        "CARGO_TARGET_DIR",
+        "CARGO_HOME",
+        "RUSTC_WRAPPER",
+    ] {
+        if envs.iter().any(|(set, _)| set == key) {
+            continue;
+        }
+        if let Ok(value) = env::var(key) {
+            write!(command, "\n# inherited: {}={}", key, shell_quote(&value)).unwrap();
+        }
+    }
+    command
+}
+
+/// `s`, single-quoted if it has anything a shell would interpret.
+fn shell_quote(s: &str) -> String {
+    let plain = !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_=+,./:@".contains(c));
+    if plain {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', "'\\''"))
+    }
+}
+
+/// Waits for `child` to exit, killing it if it's still running after `timeout`.
+fn wait_with_timeout(
+    child: &mut Child,
+    timeout: Duration,
+) -> Result<ExitStatus, SpirvBuilderError> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait().expect("failed to wait for cargo build") {
+            return Ok(status);
+        }
+        if Instant::now() >= deadline {
+            // This only fails if the build has exited in the meantime, which is just as well.
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(SpirvBuilderError::BuildTimeout(timeout));
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
 #[derive(Deserialize)]
 struct RustcOutput {
     reason: String,
@@ -533,3 +1013,58 @@ fn leaf_deps(artifact: &Path, mut handle: impl FnMut(&RawStr)) -> std::io::Resul
     recurse(&deps_map, artifact.to_str().unwrap().into(), &mut handle);
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn module_sizes() {
+        let dir = env::temp_dir().join(format!("spirv-builder-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (small, large) = (dir.join("small.spv"), dir.join("large.spv"));
+        std::fs::write(&small, [0; 4]).unwrap();
+        std::fs::write(&large, [0; 8]).unwrap();
+
+        let single = ModuleResult::SingleModule(small.clone());
+        assert!(check_module_sizes(&single, 4).is_ok());
+        assert!(matches!(
+            check_module_sizes(&single, 3),
+            Err(SpirvBuilderError::ModuleTooLarge { module, size: 4, max_size: 3 })
+                if module == small
+        ));
+
+        // Every module of a multimodule build has to fit on its own, not all of them together.
+        let multi = ModuleResult::MultiModule(
+            vec![
+                ("small".to_string(), small.clone()),
+                ("large".to_string(), large.clone()),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        assert!(check_module_sizes(&multi, 8).is_ok());
+        assert!(matches!(
+            check_module_sizes(&multi, 4),
+            Err(SpirvBuilderError::ModuleTooLarge { module, size: 8, max_size: 4 })
+                if module == large
+        ));
+
+        let missing = dir.join("missing.spv");
+        assert!(matches!(
+            check_module_sizes(&ModuleResult::SingleModule(missing.clone()), 4),
+            Err(SpirvBuilderError::ModuleMissing { module, .. }) if module == missing
+        ));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn shell_quoting() {
+        assert_eq!(shell_quote("--target-dir=a/b.c"), "--target-dir=a/b.c");
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("a b"), "'a b'");
+        assert_eq!(shell_quote("$HOME"), "'$HOME'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+    }
+}
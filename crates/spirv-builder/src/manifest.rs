@@ -0,0 +1,108 @@
+//! A manifest of built shaders, for asset pipelines to find the right binaries at runtime.
+
+use crate::{CompileResult, ModuleResult};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// The version of the [`Manifest`] schema, bumped whenever a field is removed or changes meaning.
+/// Adding fields doesn't bump it, so readers should ignore fields they don't know.
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// The shaders of one or more builds, as written to e.g. `shaders.json`:
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "shaders": {
+///     "sky-shader": {
+///       "path": "/path/to/sky_shader.spv",
+///       "entry_points": ["main_fs", "main_vs"],
+///       "capabilities": ["Shader"],
+///       "target": "spirv-unknown-vulkan1.1",
+///       "hash": "6c62272e07bb0142"
+///     }
+///   }
+/// }
+/// ```
+///
+/// With `multimodule`, each entry point has a module of its own, which is listed as
+/// `<shader>.<entry point>`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    pub shaders: BTreeMap<String, ShaderArtifact>,
+}
+
+/// A module listed in a [`Manifest`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShaderArtifact {
+    pub path: PathBuf,
+    pub entry_points: Vec<String>,
+    /// The capabilities declared by the module, by name.
+    pub capabilities: Vec<String>,
+    pub target: String,
+    /// A 64-bit FNV-1a hash of the module's bytes, in hex, to tell whether a binary changed.
+    pub hash: String,
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Self {
+            version: MANIFEST_VERSION,
+            shaders: BTreeMap::new(),
+        }
+    }
+}
+
+impl Manifest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lists the module (or modules) of `result` under `name`, replacing what was listed under
+    /// that name before. Fails if a module can't be read to hash it.
+    pub fn add(&mut self, name: &str, result: &CompileResult) -> std::io::Result<()> {
+        match &result.module {
+            ModuleResult::SingleModule(path) => {
+                let artifact = artifact(path, result.entry_points.clone(), result)?;
+                self.shaders.insert(name.to_string(), artifact);
+            }
+            ModuleResult::MultiModule(paths) => {
+                for (entry_point, path) in paths {
+                    let artifact = artifact(path, vec![entry_point.clone()], result)?;
+                    self.shaders
+                        .insert(format!("{}.{}", name, entry_point), artifact);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the manifest to `path` as pretty-printed JSON.
+    pub fn write(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)
+    }
+}
+
+fn artifact(
+    path: &Path,
+    entry_points: Vec<String>,
+    result: &CompileResult,
+) -> std::io::Result<ShaderArtifact> {
+    let bytes = std::fs::read(path)?;
+    Ok(ShaderArtifact {
+        path: path.to_path_buf(),
+        entry_points,
+        capabilities: result.capabilities.clone(),
+        target: result.target.clone(),
+        hash: format!("{:016x}", fnv1a(&bytes)),
+    })
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
@@ -325,3 +325,94 @@ fn path_from_ident(ident: Ident) -> syn::Type {
         path: syn::Path::from(ident),
     })
 }
+
+/// Prints a formatted string with the `NonSemantic.DebugPrintf` extended instruction set, e.g.
+/// `debug_printf!("%u: %f\n", index, value)`.
+///
+/// The format string follows the `DebugPrintf` rules, which are close to C's `printf`: `%d`,
+/// `%u`, `%x` and `%f` for 32-bit integers and floats, and e.g. `%v3f` for vectors of them. There
+/// is no checking that the arguments match the format, nor any output unless something picks it
+/// up, usually the Vulkan validation layer with `VK_VALIDATION_FEATURE_ENABLE_DEBUG_PRINTF_EXT`
+/// enabled.
+///
+/// The expansion uses `asm!`, so the shader crate needs `#![feature(asm)]`, and the module needs
+/// the `SPV_KHR_non_semantic_info` extension, e.g. from `SpirvBuilder::extension`. On the CPU,
+/// this does nothing.
+#[proc_macro]
+pub fn debug_printf(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DebugPrintfInput);
+    debug_printf_inner(input.format_string.value(), input.variables).into()
+}
+
+/// Like [`debug_printf!`], with a newline appended to the format string.
+#[proc_macro]
+pub fn debug_printfln(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DebugPrintfInput);
+    debug_printf_inner(input.format_string.value() + "\n", input.variables).into()
+}
+
+struct DebugPrintfInput {
+    format_string: syn::LitStr,
+    variables: Vec<syn::Expr>,
+}
+
+impl syn::parse::Parse for DebugPrintfInput {
+    fn parse(input: syn::parse::ParseStream<'_>) -> syn::Result<Self> {
+        let format_string = input.parse()?;
+        let mut variables = Vec::new();
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            variables.push(input.parse()?);
+        }
+        Ok(Self {
+            format_string,
+            variables,
+        })
+    }
+}
+
+fn debug_printf_inner(
+    format_string: String,
+    variables: Vec<syn::Expr>,
+) -> proc_macro2::TokenStream {
+    let mut op_loads = Vec::new();
+    let mut operands = Vec::new();
+    let mut input_registers = Vec::new();
+    for (i, variable) in variables.iter().enumerate() {
+        let ident = quote::format_ident!("_{}", i);
+        // The arguments are passed by reference, as `asm!` can't take values of every type.
+        op_loads.push(format!("%{0} = OpLoad _ {{{0}}}", ident));
+        operands.push(format!("%{}", ident));
+        input_registers.push(quote::quote! { #ident = in(reg) &#variable, });
+    }
+    // `asm!` would take braces in the string for operands.
+    let op_string = format!("%string = OpString {:?}", format_string)
+        .replace('{', "{{")
+        .replace('}', "}}");
+    let op_ext_inst = format!(
+        "%result = OpExtInst %void %debug_printf 1 %string {}",
+        operands.join(" ")
+    );
+    quote::quote! {
+        {
+            #[cfg(target_arch = "spirv")]
+            unsafe {
+                asm!(
+                    "%void = OpTypeVoid",
+                    "%debug_printf = OpExtInstImport \"NonSemantic.DebugPrintf\"",
+                    #op_string,
+                    #(#op_loads,)*
+                    #op_ext_inst,
+                    #(#input_registers)*
+                );
+            }
+            #[cfg(not(target_arch = "spirv"))]
+            {
+                let _ = (#(&#variables,)*);
+            }
+        }
+    }
+}
@@ -99,6 +99,7 @@ pub mod vector;
 
 pub use self::sampler::Sampler;
 pub use crate::macros::Image;
+pub use crate::macros::{debug_printf, debug_printfln};
 pub use num_traits;
 pub use runtime_array::*;
 pub use textures::*;
@@ -1,4 +1,4 @@
-use spirv_builder::{Capability, SpirvBuilder};
+use spirv_builder::{Capability, CompileResult, Manifest, SpirvBuilder};
 use std::env;
 use std::error::Error;
 use std::fs;
@@ -8,7 +8,7 @@ fn build_shader(
     path_to_crate: &str,
     codegen_names: bool,
     caps: &[Capability],
-) -> Result<(), Box<dyn Error>> {
+) -> Result<CompileResult, Box<dyn Error>> {
     let builder_dir = &Path::new(env!("CARGO_MANIFEST_DIR"));
     let path_to_crate = builder_dir.join(path_to_crate);
     let mut builder = SpirvBuilder::new(path_to_crate, "spirv-unknown-vulkan1.1");
@@ -22,18 +22,35 @@ fn build_shader(
         fs::create_dir_all(&out_dir).unwrap();
         fs::write(&dest_path, result.codegen_entry_point_strings()).unwrap();
     }
-    Ok(())
+    Ok(result)
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-    build_shader("../../../shaders/sky-shader", true, &[])?;
-    build_shader("../../../shaders/simplest-shader", false, &[])?;
+    let mut manifest = Manifest::new();
+    manifest.add(
+        "sky-shader",
+        &build_shader("../../../shaders/sky-shader", true, &[])?,
+    )?;
+    manifest.add(
+        "simplest-shader",
+        &build_shader("../../../shaders/simplest-shader", false, &[])?,
+    )?;
     // We need the int8 capability for using `Option`
-    build_shader(
-        "../../../shaders/compute-shader",
-        false,
-        &[Capability::Int8],
+    manifest.add(
+        "compute-shader",
+        &build_shader(
+            "../../../shaders/compute-shader",
+            false,
+            &[Capability::Int8],
+        )?,
     )?;
-    build_shader("../../../shaders/mouse-shader", false, &[])?;
+    manifest.add(
+        "mouse-shader",
+        &build_shader("../../../shaders/mouse-shader", false, &[])?,
+    )?;
+    // Lists every shader's binary for tooling, next to the generated entry point names.
+    if let Some(out_dir) = env::var_os("OUT_DIR") {
+        manifest.write(Path::new(&out_dir).join("shaders.json"))?;
+    }
     Ok(())
 }
@@ -0,0 +1,6 @@
+fn main() {
+    #[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+    if !example_runner_wgpu::smoke() {
+        std::process::exit(1);
+    }
+}
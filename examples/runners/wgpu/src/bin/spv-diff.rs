@@ -0,0 +1,4 @@
+fn main() {
+    #[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+    std::process::exit(example_runner_wgpu::spv_diff());
+}
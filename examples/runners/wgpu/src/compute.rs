@@ -1,8 +1,30 @@
-use wgpu::util::DeviceExt;
+use super::{Options, Verbosity};
+use crate::reflect::{Reflection, SourceLocation};
+use crate::texture::read_png;
+use crate::trace::Trace;
+use crate::SpirvModule;
+use futures::future::FutureExt;
+use rspirv::binary::ParseState;
+use rspirv::spirv::{Capability, ExecutionModel};
+use serde::{Serialize, Serializer};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufWriter, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::{fmt, future::Future};
 
-use super::Options;
-use futures::future::join;
-use std::{convert::TryInto, future::Future, num::NonZeroU64, time::Duration};
+mod context;
+
+use context::{
+    aligned_buffer_size, feature_names, is_8bit_storage, local_size, max_workgroup_storage,
+    padded_len, reflect, swap_words, workgroup_counts,
+};
+pub use context::{poll_once, ComputeContext, Submission};
 
 fn block_on<T>(future: impl Future<Output = T>) -> T {
     cfg_if::cfg_if! {
@@ -14,180 +36,1459 @@ fn block_on<T>(future: impl Future<Output = T>) -> T {
     }
 }
 
+/// How long `--loop` waits for a rebuilt shader before re-running the current one.
+const LOOP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Runs the compute shader as `options` asks, panicking on failure. See [`try_start`] for a
+/// version that returns failures instead.
 pub fn start(options: &Options) {
-    let rx = crate::maybe_watch(options.shader, true);
-    let shader_binary = rx.recv().expect("Should send one binary");
+    // On the web the page stays open regardless, and there's no rebuilding to wait for.
+    let interactive = (options.keep_running || options.repl) && !cfg!(target_arch = "wasm32");
+    let result = if options.dry_run {
+        dry_run(options)
+    } else if let Some(max_runs) = options.repeat_until_diff {
+        load_shader(options)
+            .and_then(|shader_binary| block_on(repeat_until_diff(options, shader_binary, max_runs)))
+    } else if options.compare_passthrough {
+        load_shader(options)
+            .and_then(|shader_binary| block_on(compare_passthrough(options, shader_binary)))
+    } else if interactive {
+        block_on(run_interactive(options))
+    } else {
+        try_start(options).map(|_| ())
+    };
+    if let Err(err) = result {
+        panic!("{}", err);
+    }
+}
 
-    block_on(start_internal(options, shader_binary))
+/// Builds the shader (or loads the `--spv-file`) and runs it once.
+///
+/// Unlike [`start`], this never panics on failure, for callers that have to outlive a failed
+/// build, a missing adapter, or a dispatch gone wrong.
+pub fn try_start(options: &Options) -> Result<ComputeOutput, RunnerError> {
+    let trace = Trace::new(options);
+    let build = trace.start();
+    let shader_binary = load_shader(options)?;
+    trace.end("Build", build);
+    block_on(start_traced(options, shader_binary, trace))
+}
+
+/// Like [`try_start`], but runs the shader over `len` inputs made by `generate` from their index,
+/// rather than over the Collatz starting values, e.g. `|i| i.wrapping_mul(2654435761)` to feed it
+/// scattered values, or `|i| data[i as usize]` to feed it existing ones.
+pub fn start_with_generator(
+    options: &Options,
+    len: u32,
+    generate: impl FnMut(u32) -> u32,
+) -> Result<ComputeOutput, RunnerError> {
+    let trace = Trace::new(options);
+    let build = trace.start();
+    let shader_binary = load_shader(options)?;
+    trace.end("Build", build);
+    let src = (0..len).map(generate).collect::<Vec<_>>();
+    block_on(async {
+        let context = ComputeContext::with_trace(options, trace).await?;
+        run_src(&context, options, &shader_binary, &src).await
+    })
+}
+
+/// Runs [`try_start`] on a thread of its own, sending back its result once it's done, for
+/// applications whose main thread can't block on the GPU, e.g. to keep a UI responsive.
+///
+/// Every wgpu resource is created and dropped on that thread, so the output has no
+/// [`ComputeOutput::pipeline`]. The channel is closed without a result if the thread panics.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn spawn_compute(options: Options) -> Receiver<Result<ComputeOutput, RunnerError>> {
+    let (tx, rx) = std::sync::mpsc::sync_channel(1);
+    std::thread::Builder::new()
+        .name("compute".to_string())
+        .spawn(move || {
+            let result = try_start(&options).map(|mut output| {
+                output.pipeline = None;
+                output
+            });
+            // Nothing to do if the receiver has stopped listening.
+            let _ = tx.send(result);
+        })
+        .expect("Failed to spawn the compute thread");
+    rx
+}
+
+/// Builds the shader, or loads the `--spv-file`, without watching it for changes.
+fn load_shader(options: &Options) -> Result<wgpu::ShaderModuleDescriptor<'static>, RunnerError> {
+    match &options.spv_file {
+        Some(path) => load_spv_file(path, &options.entry_point),
+        // The compiler has already reported why a build failed, all that's left is to stop.
+        None => crate::maybe_watch(options, true)
+            .recv()
+            .map_err(|_| RunnerError::BuildFailed),
+    }
+}
+
+/// `--dry-run`: prints what would be bound, dispatched and allocated for the shader, without
+/// creating a device, so the build and reflection can be checked on machines without a GPU.
+fn dry_run(options: &Options) -> Result<(), RunnerError> {
+    let shader_binary = load_shader(options)?;
+    let reflection = reflect(&shader_binary)?;
+    let len = DEFAULT_SRC_RANGE.len();
+    let local_size = local_size(reflection.as_ref(), &options.entry_point);
+    let workgroups = workgroup_counts(options, len, local_size)?;
+    let padded_len = padded_len(options, len, workgroups, local_size);
+
+    match &reflection {
+        Some(reflection) => {
+            for binding in &reflection.bindings {
+                let size = match binding.size {
+                    Some(size) if binding.runtime_array => {
+                        format!("{} bytes + runtime array", size)
+                    }
+                    Some(size) => format!("{} bytes", size),
+                    None => "unsized".to_string(),
+                };
+                println!(
+                    "Binding {}.{}: {:?} in {:?} ({})",
+                    binding.set, binding.binding, binding.kind, binding.storage_class, size
+                );
+            }
+        }
+        None => println!("Bindings: unknown, WGSL can't be reflected"),
+    }
+    println!(
+        "Dispatch: {:?} workgroups of {:?}, {} times ({} warmup)",
+        workgroups,
+        local_size,
+        warmup_count(options) + options.iterations.max(1),
+        warmup_count(options),
+    );
+    if let Some(reflection) = &reflection {
+        println!(
+            "Workgroup storage: {} of {} bytes",
+            reflection.workgroup_storage,
+            max_workgroup_storage(options)
+        );
+    }
+    println!(
+        "Storage buffer: {} bytes ({} elements, padded to {})",
+        aligned_buffer_size(padded_len * 4),
+        len,
+        padded_len
+    );
+    println!(
+        "Timestamp buffer: {} bytes",
+        16 * u64::from(options.iterations.max(1))
+    );
+    Ok(())
+}
+
+/// The workgroup shared memory every device is assumed to have without `--max-workgroup-storage`,
+/// the least Vulkan guarantees for `maxComputeSharedMemorySize` and WebGPU for
+/// `maxComputeWorkgroupStorageSize`. This version of wgpu doesn't report a device's actual limit.
+const DEFAULT_MAX_WORKGROUP_STORAGE: u64 = 16384;
+
+/// How many differing elements `--repeat-until-diff` lists before summarizing the rest.
+const MAX_REPORTED_DIFFS: usize = 16;
+
+/// Dispatches the shader up to `max_runs` times on the same device, failing at the first run
+/// whose results differ from the first run's.
+async fn repeat_until_diff(
+    options: &Options,
+    shader_binary: wgpu::ShaderModuleDescriptor<'static>,
+    max_runs: u32,
+) -> Result<(), RunnerError> {
+    let context = ComputeContext::new(options).await?;
+    let src = DEFAULT_SRC_RANGE.collect::<Vec<_>>();
+    let first = context
+        .dispatch(options, &shader_binary, &src)
+        .await?
+        .result;
+    for run in 1..max_runs {
+        let result = context
+            .dispatch(options, &shader_binary, &src)
+            .await?
+            .result;
+        let diffs = differing_elements(&first, &result);
+        if diffs.is_empty() {
+            continue;
+        }
+        println!(
+            "Run {} differs from the first run in {} elements:",
+            run + 1,
+            diffs.len()
+        );
+        print_diffs(&diffs, |before, after| format!("{} then {}", before, after));
+        return Err(RunnerError::OutputChanged {
+            run: run + 1,
+            elements: diffs.len(),
+        });
+    }
+    println!(
+        "All {} runs gave the same results, hashing to {}",
+        max_runs.max(1),
+        result_hash(&first)
+    );
+    Ok(())
+}
+
+/// `--compare-passthrough`: dispatches the same module on the same device validated with naga,
+/// then passed through to the driver, failing if their results differ.
+///
+/// With a single module and device, a difference comes down to naga's translation of the module
+/// or to the driver's handling of the SPIR-V naga would have rejected or rewritten.
+async fn compare_passthrough(
+    options: &Options,
+    shader_binary: wgpu::ShaderModuleDescriptor<'static>,
+) -> Result<(), RunnerError> {
+    let context = ComputeContext::new(options).await?;
+    let backend = context.adapter().get_info().backend;
+    if backend != wgpu::Backend::Vulkan {
+        return Err(RunnerError::PassthroughUnsupported(backend));
+    }
+    let src = DEFAULT_SRC_RANGE.collect::<Vec<_>>();
+    let naga = context
+        .dispatch_module(options, &shader_binary, &src, false)
+        .await?
+        .result;
+    let passthrough = context
+        .dispatch_module(options, &shader_binary, &src, true)
+        .await?
+        .result;
+    let diffs = differing_elements(&naga, &passthrough);
+    if diffs.is_empty() {
+        println!(
+            "Naga and passthrough gave the same results, hashing to {}",
+            result_hash(&naga)
+        );
+        return Ok(());
+    }
+    println!("Passthrough differs from naga in {} elements:", diffs.len());
+    print_diffs(&diffs, |naga, passthrough| {
+        format!("{} with naga, {} passed through", naga, passthrough)
+    });
+    println!(
+        "Results hash to {} with naga, {} passed through",
+        result_hash(&naga),
+        result_hash(&passthrough)
+    );
+    Err(RunnerError::PassthroughDiffers {
+        elements: diffs.len(),
+    })
+}
+
+/// The index, and the value in `a` and in `b`, of every element where they differ.
+fn differing_elements(a: &[u32], b: &[u32]) -> Vec<(usize, u32, u32)> {
+    a.iter()
+        .zip(b)
+        .enumerate()
+        .filter(|(_, (a, b))| a != b)
+        .map(|(index, (&a, &b))| (index, a, b))
+        .collect()
+}
+
+/// Lists the first [`MAX_REPORTED_DIFFS`] of `diffs`, each value pair as `describe` puts it.
+fn print_diffs(diffs: &[(usize, u32, u32)], describe: impl Fn(u32, u32) -> String) {
+    for &(index, a, b) in diffs.iter().take(MAX_REPORTED_DIFFS) {
+        println!("  [{}]: {}", index, describe(a, b));
+    }
+    if diffs.len() > MAX_REPORTED_DIFFS {
+        println!("  ... and {} more", diffs.len() - MAX_REPORTED_DIFFS);
+    }
+}
+
+/// Runs the shader with `--repl`, or every [`LOOP_INTERVAL`] with `--loop`.
+async fn run_interactive(options: &Options) -> Result<(), RunnerError> {
+    let (shader_binary, rx) = match &options.spv_file {
+        // A precompiled module has nothing to build, nor any source to watch.
+        Some(path) => (load_spv_file(path, &options.entry_point)?, None),
+        None => {
+            let rx = crate::maybe_watch(options, !options.keep_running);
+            let shader_binary = rx.recv().map_err(|_| RunnerError::BuildFailed)?;
+            (shader_binary, Some(rx))
+        }
+    };
+    match rx {
+        _ if options.repl => run_repl(options, &shader_binary).await,
+        Some(rx) => run_loop(options, shader_binary, rx).await,
+        None => start_internal(options, shader_binary).await.map(|_| ()),
+    }
+}
+
+/// Loads the SPIR-V module at `path`, checking that it has the compute entry point to run.
+fn load_spv_file(
+    path: &Path,
+    entry_point: &str,
+) -> Result<wgpu::ShaderModuleDescriptor<'static>, RunnerError> {
+    let words = crate::read_spirv(path).map_err(RunnerError::SpvLoadFailed)?;
+    let reflection = Reflection::new(&words).map_err(RunnerError::InvalidSpirv)?;
+    if reflection
+        .entry_point(entry_point, ExecutionModel::GLCompute)
+        .is_none()
+    {
+        return Err(RunnerError::MissingEntryPoint(entry_point.to_string()));
+    }
+    Ok(SpirvModule::new(words).into_descriptor())
 }
 
 pub async fn start_internal(
-    _options: &Options,
+    options: &Options,
     shader_binary: wgpu::ShaderModuleDescriptor<'static>,
-) {
-    let instance = wgpu::Instance::new(wgpu::BackendBit::PRIMARY);
-    let adapter = instance
-        .request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::default(),
-            compatible_surface: None,
-        })
-        .await
-        .expect("Failed to find an appropriate adapter");
-
-    let timestamp_period = adapter.get_timestamp_period();
-    let (device, queue) = adapter
-        .request_device(
-            &wgpu::DeviceDescriptor {
-                label: None,
-                features: wgpu::Features::TIMESTAMP_QUERY,
-                limits: wgpu::Limits::default(),
+) -> Result<ComputeOutput, RunnerError> {
+    start_traced(options, shader_binary, Trace::new(options)).await
+}
+
+/// Like [`start_internal`], adding to a `trace` that may already have the build in it.
+async fn start_traced(
+    options: &Options,
+    shader_binary: wgpu::ShaderModuleDescriptor<'static>,
+    trace: Trace,
+) -> Result<ComputeOutput, RunnerError> {
+    // One-shot runs just use a throwaway context.
+    let context = ComputeContext::with_trace(options, trace).await?;
+    run_once(&context, options, &shader_binary).await
+}
+
+/// Re-runs the shader every [`LOOP_INTERVAL`], switching to newly rebuilt shaders as soon as they
+/// arrive on `rx`.
+async fn run_loop(
+    options: &Options,
+    mut shader_binary: wgpu::ShaderModuleDescriptor<'static>,
+    rx: Receiver<wgpu::ShaderModuleDescriptor<'static>>,
+) -> Result<(), RunnerError> {
+    let context = ComputeContext::new(options).await?;
+    loop {
+        if let Err(err) = run_once(&context, options, &shader_binary).await {
+            eprintln!("{}", err);
+        }
+        match rx.recv_timeout(LOOP_INTERVAL) {
+            Ok(new_shader_binary) => shader_binary = new_shader_binary,
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+    }
+}
+
+const REPL_HELP: &str = "\
+Commands:
+  range <start> <end>  dispatch over the inputs start..end (start must be at least 1)
+  run                  dispatch again over the current range (as does an empty line)
+  quit                 exit
+The workgroup size is fixed by the shader, the number of workgroups follows from the range
+(or --dispatch-dims).";
+
+/// Reads commands from stdin, re-dispatching `shader_binary` on the same context for each of
+/// them, until stdin is closed or `quit` is entered.
+async fn run_repl(
+    options: &Options,
+    shader_binary: &wgpu::ShaderModuleDescriptor<'_>,
+) -> Result<(), RunnerError> {
+    let context = ComputeContext::new(options).await?;
+    let mut src_range = DEFAULT_SRC_RANGE;
+    println!("{}", REPL_HELP);
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("> ");
+        let _ = std::io::stdout().flush();
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => return Ok(()),
+        };
+        match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [] | ["run"] => {}
+            ["range", start, end] => match (start.parse::<u32>(), end.parse::<u32>()) {
+                (Ok(start), Ok(end)) if 0 < start && start < end => src_range = start..end,
+                _ => {
+                    eprintln!("Expected `range <start> <end>`, with 0 < start < end");
+                    continue;
+                }
             },
-            None,
+            ["quit"] | ["exit"] => return Ok(()),
+            _ => {
+                eprintln!("{}", REPL_HELP);
+                continue;
+            }
+        }
+        if let Err(err) = run_range(&context, options, shader_binary, src_range.clone()).await {
+            eprintln!("{}", err);
+        }
+    }
+}
+
+/// The inputs the shader is run over, unless changed in the `--repl`.
+const DEFAULT_SRC_RANGE: Range<u32> = 1..1 << 20;
+
+async fn run_once(
+    context: &ComputeContext,
+    options: &Options,
+    shader_binary: &wgpu::ShaderModuleDescriptor<'_>,
+) -> Result<ComputeOutput, RunnerError> {
+    run_range(context, options, shader_binary, DEFAULT_SRC_RANGE).await
+}
+
+async fn run_range(
+    context: &ComputeContext,
+    options: &Options,
+    shader_binary: &wgpu::ShaderModuleDescriptor<'_>,
+    src_range: Range<u32>,
+) -> Result<ComputeOutput, RunnerError> {
+    let src = src_range.collect::<Vec<_>>();
+    run_src(context, options, shader_binary, &src).await
+}
+
+/// Runs the shader over `src`, reporting the results as `options` asks.
+async fn run_src(
+    context: &ComputeContext,
+    options: &Options,
+    shader_binary: &wgpu::ShaderModuleDescriptor<'_>,
+    src: &[u32],
+) -> Result<ComputeOutput, RunnerError> {
+    let mut output = context.dispatch(options, shader_binary, src).await?;
+    if options.sanity {
+        warn_if_unchanged(options, src, &output);
+    }
+    if options.entry_point == SCAN_ENTRY_POINT {
+        carry_workgroup_maxima(&mut output.result, output.local_size);
+    }
+    // `OptionsBuilder::build` checks the size is given along with the path.
+    if let (Some(path), Some(width), Some(height)) = (&options.image, options.width, options.height)
+    {
+        if let Err(err) = write_image(path, width, height, &output.result) {
+            eprintln!("Failed to write {}: {}", path.display(), err);
+        }
+    }
+    for appended in &output.appended {
+        if appended.count > appended.capacity {
+            eprintln!(
+                "Warning: binding {} had {} elements appended, but only room for {}",
+                appended.binding, appended.count, appended.capacity
+            );
+        } else if !options.json {
+            println!(
+                "Binding {}: {} elements appended",
+                appended.binding, appended.count
+            );
+        }
+    }
+    if let (Some(path), false) = (&options.output_mmap, options.json) {
+        println!(
+            "Results: {} bytes written to {}",
+            src.len() * 4,
+            path.display()
+        );
+    }
+    if let (Some(count), false) = (output.count, options.json) {
+        println!("Count: {}", count);
+    }
+    for file in &options.output {
+        let mut contents = if file.binding == 0 {
+            result_bytes(&output.result)
+        } else {
+            output
+                .outputs
+                .iter()
+                .find(|(binding, _)| *binding == file.binding)
+                .map_or_else(Vec::new, |(_, contents)| contents.clone())
+        };
+        swap_words(options.input_endian, &mut contents);
+        let written = std::fs::write(&file.path, contents);
+        if let Err(err) = written {
+            eprintln!("Failed to write {}: {}", file.path.display(), err);
+        }
+    }
+    if let (Some(path), Some(width), Some(height)) =
+        (&options.expect, options.width, options.height)
+    {
+        check_expected_image(path, options.tolerance, width, height, &output.result)?;
+    }
+    if !options.debug_index.is_empty() {
+        match &output.debug {
+            Some(debug) => debug.print(&options.debug_index),
+            None => eprintln!(
+                "Warning: --debug-index was given, but the shader has no debug buffer at \
+                 binding {}",
+                DEBUG_BINDING
+            ),
+        }
+    }
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&output).unwrap());
+        return Ok(output);
+    }
+    match (&output.hash, &output.float_summary) {
+        (Some(hash), _) => println!("Hash: {}", hash),
+        (None, Some(summary)) => {
+            println!(
+                "{} floats: min {:?}, max {:?}, mean {:?}",
+                summary.len, summary.min, summary.max, summary.mean
+            );
+            // Non-finite results are almost always a bug in the kernel.
+            if summary.nan_count > 0 || summary.infinite_count > 0 {
+                eprintln!(
+                    "Warning: {} NaN and {} infinite values in the output",
+                    summary.nan_count, summary.infinite_count
+                );
+            }
+        }
+        (None, None) => {
+            let mut max = 0;
+            for (&src, out) in src.iter().zip(output.result.iter().copied()) {
+                if out == u32::MAX {
+                    println!("{}: overflowed", src);
+                    break;
+                } else if out > max {
+                    max = out;
+                    // Should produce <https://oeis.org/A006877>
+                    println!("{}: {}", src, out);
+                }
+            }
+        }
+    }
+    let verbosity = options.verbosity();
+    if verbosity == Verbosity::Quiet {
+        return Ok(output);
+    }
+    if verbosity >= Verbosity::Verbose {
+        let adapter = &output.adapter;
+        println!(
+            "Adapter: {} ({}, {}), selected by {}",
+            adapter.name, adapter.device_type, adapter.backend, adapter.selected_by
+        );
+        match adapter.subgroup_size {
+            Some(subgroup_size) => println!("Subgroup size: {}", subgroup_size),
+            None => println!(
+                "Subgroup size: not exposed by the {} backend",
+                adapter.backend
+            ),
+        }
+        if options.passthrough && !output.passthrough {
+            println!(
+                "Passthrough: not supported on {}, validated with naga instead",
+                adapter.backend
+            );
+        }
+        println!("Timestamps: written {}", output.timestamps);
+        println!("Readback: {}", output.readback);
+    }
+    if verbosity >= Verbosity::Debug {
+        println!("Features: {}", output.features.join(", "));
+        #[cfg(all(feature = "memory-report", not(target_arch = "wasm32")))]
+        println!("Resources: {}", context.memory_report());
+    }
+    println!(
+        "Dispatched {:?} workgroups of {:?} ({} invocations)",
+        output.workgroups, output.local_size, output.invocations
+    );
+    if output.invocations < output.result.len() as u64 {
+        println!(
+            "Only the first {} of {} elements were processed",
+            output.invocations,
+            output.result.len()
+        );
+    }
+    let timing = &output.timing;
+    if output.gpu_times.len() == 1 {
+        println!("Took: {:?}", timing.min);
+    } else {
+        println!(
+            "Took: min {:?}, mean {:?}, max {:?} over {} iterations ({} warmup)",
+            timing.min,
+            timing.mean,
+            timing.max,
+            output.gpu_times.len(),
+            output.warmup,
+        );
+        println!(
+            "      p50 {:?}, p95 {:?}, p99 {:?}",
+            timing.p50, timing.p95, timing.p99
+        );
+        if verbosity >= Verbosity::Debug {
+            for (i, time) in output.gpu_times.iter().enumerate() {
+                println!("      iteration {}: {:?}", i, time);
+            }
+        }
+    }
+    Ok(output)
+}
+
+/// Writes `result` to a PNG at `path`, treating each element as one RGBA8 pixel.
+fn write_image(path: &Path, width: u32, height: u32, result: &[u32]) -> Result<(), Box<dyn Error>> {
+    write_png(path, width, height, &image_data(width, height, result)?)
+}
+
+/// The bytes of `result`, as `width` x `height` RGBA8 pixels.
+fn image_data(width: u32, height: u32, result: &[u32]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let pixels = u64::from(width) * u64::from(height);
+    if pixels != result.len() as u64 {
+        return Err(format!(
+            "{}x{} RGBA8 pixels need {} bytes, but the output is {} bytes",
+            width,
+            height,
+            pixels * 4,
+            result.len() * 4
         )
-        .await
-        .expect("Failed to create device");
-    drop(instance);
-    drop(adapter);
-    // Load the shaders from disk
-    let module = device.create_shader_module(&shader_binary);
-
-    let top = 2u32.pow(20);
-    let src_range = 1..top;
-
-    let src = src_range
-        .clone()
-        // Not sure which endianness is correct to use here
+        .into());
+    }
+    Ok(result_bytes(result))
+}
+
+/// The bytes of `result`, exactly as the shader wrote them.
+fn result_bytes(result: &[u32]) -> Vec<u8> {
+    result
+        .iter()
+        .copied()
         .map(u32::to_ne_bytes)
         .flat_map(core::array::IntoIter::new)
-        .collect::<Vec<_>>();
-
-    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        label: None,
-        entries: &[
-            // XXX - some graphics cards do not support empty bind layout groups, so
-            // create a dummy entry.
-            wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                count: None,
-                visibility: wgpu::ShaderStage::COMPUTE,
-                ty: wgpu::BindingType::Buffer {
-                    has_dynamic_offset: false,
-                    min_binding_size: Some(NonZeroU64::new(1).unwrap()),
-                    ty: wgpu::BufferBindingType::Storage { read_only: false },
-                },
+        .collect()
+}
+
+fn write_png(path: &Path, width: u32, height: u32, data: &[u8]) -> Result<(), Box<dyn Error>> {
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width, height);
+    encoder.set_color(png::ColorType::RGBA);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(data)?;
+    Ok(())
+}
+
+/// A 64-bit FNV-1a hash of `result`, in hex.
+///
+/// The elements are hashed as little-endian bytes whatever the host's endianness, and FNV-1a is
+/// fixed rather than seeded per process, so hashes are comparable across runs and machines.
+fn result_hash(result: &[u32]) -> String {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    let hash = result
+        .iter()
+        .flat_map(|value| core::array::IntoIter::new(value.to_le_bytes()))
+        .fold(OFFSET_BASIS, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+        });
+    format!("{:016x}", hash)
+}
+
+/// Compares the image in `result` against the `--expect` PNG at `path`.
+///
+/// Pixels match if none of their channels differ by more than `tolerance`, to allow for drivers
+/// rounding differently. On a mismatch, a diff image with the mismatched pixels in red is written
+/// next to the reference.
+fn check_expected_image(
+    path: &Path,
+    tolerance: u8,
+    width: u32,
+    height: u32,
+    result: &[u32],
+) -> Result<(), RunnerError> {
+    let load_failed = |err: Box<dyn Error>| RunnerError::ExpectedImage(err.to_string());
+    let actual = image_data(width, height, result).map_err(load_failed)?;
+    let (expected_width, expected_height, expected) = read_png(path).map_err(load_failed)?;
+    if (expected_width, expected_height) != (width, height) {
+        return Err(RunnerError::ExpectedImage(format!(
+            "{} is {}x{}, but the output is {}x{}",
+            path.display(),
+            expected_width,
+            expected_height,
+            width,
+            height
+        )));
+    }
+    let mut mismatched = 0;
+    let diff = actual
+        .chunks_exact(4)
+        .zip(expected.chunks_exact(4))
+        .flat_map(|(actual, expected)| {
+            let matches = actual.iter().zip(expected).all(|(&actual, &expected)| {
+                (i16::from(actual) - i16::from(expected)).abs() <= i16::from(tolerance)
+            });
+            if matches {
+                core::array::IntoIter::new([0, 0, 0, 255])
+            } else {
+                mismatched += 1;
+                core::array::IntoIter::new([255, 0, 0, 255])
+            }
+        })
+        .collect::<Vec<u8>>();
+    if mismatched == 0 {
+        return Ok(());
+    }
+    let diff_path = path.with_extension("diff.png");
+    // The mismatch itself is what matters, so a failure to write the diff is only reported.
+    if let Err(err) = write_png(&diff_path, width, height, &diff) {
+        eprintln!("Failed to write {}: {}", diff_path.display(), err);
+    }
+    Err(RunnerError::ImageMismatch {
+        mismatched,
+        diff: diff_path,
+    })
+}
+
+/// The number of untimed dispatches done before the timed ones, unless `--warmup` says otherwise.
+const DEFAULT_WARMUP: u32 = 3;
+
+fn warmup_count(options: &Options) -> u32 {
+    match options.warmup {
+        Some(warmup) => warmup,
+        // A single dispatch is just a run, not a benchmark.
+        None if options.iterations > 1 => DEFAULT_WARMUP,
+        None => 0,
+    }
+}
+
+/// The result of a single [`ComputeContext::dispatch`].
+#[derive(Serialize)]
+pub struct ComputeOutput {
+    /// The contents of the storage buffer after the dispatch.
+    ///
+    /// This is usually far too large to be useful when serialized, so only its
+    /// [`ComputeOutput::summary`] is.
+    #[serde(skip)]
+    pub result: Vec<u32>,
+    pub summary: ValuesSummary,
+    /// A summary of the results reinterpreted as floats, with `--output-type f32`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub float_summary: Option<FloatSummary>,
+    /// A stable 64-bit FNV-1a hash of the results in hex, with `--hash`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    /// The number of workgroups dispatched in each dimension.
+    pub workgroups: [u32; 3],
+    /// The number of invocations in each workgroup, in each dimension.
+    pub local_size: [u32; 3],
+    /// The total number of shader invocations across all workgroups.
+    pub invocations: u64,
+    /// GPU time between the timestamps written around each timed dispatch.
+    #[serde(rename = "gpu_times_ns", serialize_with = "serialize_nanos_slice")]
+    pub gpu_times: Vec<Duration>,
+    pub timing: TimingSummary,
+    /// Where the timestamps behind `gpu_times` were written.
+    pub timestamps: TimestampGranularity,
+    /// How the results were read back from the GPU.
+    pub readback: Readback,
+    /// The number of untimed dispatches done before the timed ones.
+    pub warmup: u32,
+    pub adapter: AdapterSummary,
+    /// The names of the `wgpu::Features` enabled on the device.
+    pub features: Vec<String>,
+    /// Whether the shader was handed to the driver without naga validating it, with
+    /// `--passthrough` on a backend that supports it.
+    pub passthrough: bool,
+    /// The [`Submission::index`] of the last submission the dispatch made, the one reading back
+    /// its results, to order it among other work submitted to the same [`ComputeContext`].
+    pub submission: u64,
+    /// The contents of the `--output` buffers other than binding 0, by binding.
+    /// With `--append`, only the elements the cursor counts, without the cursor.
+    #[serde(skip)]
+    pub outputs: Vec<(u32, Vec<u8>)>,
+    /// How many elements were appended to each `--append` buffer.
+    pub appended: Vec<Appended>,
+    /// The records the shader wrote to its debug buffer, if it declares one at [`DEBUG_BINDING`].
+    #[serde(skip)]
+    pub debug: Option<DebugRecords>,
+    /// The value the shader left in its counter at [`COUNT_BINDING`], with `--count-only`, whose
+    /// `result` is left empty.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<u32>,
+    /// The pipeline the shader was dispatched with, for recording further passes with it on the
+    /// [`ComputeContext`] it came from. `None` in the output of [`spawn_compute`], whose context
+    /// is gone along with its thread.
+    #[serde(skip)]
+    pub pipeline: Option<Arc<wgpu::ComputePipeline>>,
+}
+
+/// The binding of descriptor set 0 where a compute shader can declare a debug buffer, a
+/// runtime-sized array with a record per invocation, e.g.
+/// `#[spirv(storage_buffer, descriptor_set = 0, binding = 15)] debug: &mut [[u32; 4]]`.
+///
+/// The runner binds a zeroed buffer there and reads it back after the run, so the shader can
+/// write whatever it wants to look at into the record of its invocation, to be printed with
+/// `--debug-index`. This works on every backend, unlike `debug_printf!`, which needs the Vulkan
+/// validation layer. Records are indexed by the global invocation id, flattened as
+/// `x + y * width + z * width * height` over the whole dispatch.
+pub const DEBUG_BINDING: u32 = 15;
+
+/// The binding of descriptor set 0 where a compute shader declares the counter it reduces its
+/// input to with `--count-only`, e.g.
+/// `#[spirv(storage_buffer, descriptor_set = 0, binding = 1)] count: &mut u32`.
+///
+/// The runner binds it zeroed, and reads back only the counter after the run, leaving the storage
+/// buffer at binding 0 on the GPU.
+pub const COUNT_BINDING: u32 = 1;
+
+/// The contents of the debug buffer at [`DEBUG_BINDING`] after a run.
+pub struct DebugRecords {
+    /// The number of `u32`s in each record.
+    pub record_words: usize,
+    pub words: Vec<u32>,
+}
+
+impl DebugRecords {
+    /// The record of the invocation at the flattened global invocation id `index`.
+    pub fn record(&self, index: u64) -> Option<&[u32]> {
+        let start = usize::try_from(index)
+            .ok()?
+            .checked_mul(self.record_words)?;
+        self.words.get(start..start + self.record_words)
+    }
+
+    /// Prints the records of the invocations at `indices`, as hex and as floats.
+    pub fn print(&self, indices: &[u64]) {
+        for &index in indices {
+            match self.record(index) {
+                Some(record) => println!(
+                    "Invocation {}: {:08x?} as floats {:?}",
+                    index,
+                    record,
+                    record
+                        .iter()
+                        .map(|&word| f32::from_bits(word))
+                        .collect::<Vec<_>>()
+                ),
+                None => println!("Invocation {}: out of range", index),
+            }
+        }
+    }
+}
+
+/// The cursor of an `--append` buffer after the run.
+#[derive(Serialize)]
+pub struct Appended {
+    pub binding: u32,
+    /// The final value of the cursor. Past `capacity`, elements were reserved that didn't fit.
+    pub count: u32,
+    pub capacity: u32,
+}
+
+#[derive(Serialize)]
+pub struct ValuesSummary {
+    pub len: usize,
+    pub min: Option<u32>,
+    pub max: Option<u32>,
+}
+
+impl ValuesSummary {
+    fn new(values: &[u32]) -> Self {
+        Self {
+            len: values.len(),
+            min: values.iter().copied().min(),
+            max: values.iter().copied().max(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct FloatSummary {
+    pub len: usize,
+    /// The smallest and largest finite values, if there are any.
+    pub min: Option<f32>,
+    pub max: Option<f32>,
+    /// The mean of the finite values.
+    pub mean: Option<f64>,
+    pub nan_count: usize,
+    pub infinite_count: usize,
+}
+
+impl FloatSummary {
+    fn new(values: &[u32]) -> Self {
+        let floats = values.iter().map(|&bits| f32::from_bits(bits));
+        let finite = floats.clone().filter(|x| x.is_finite()).collect::<Vec<_>>();
+        Self {
+            len: values.len(),
+            min: finite.iter().copied().reduce(f32::min),
+            max: finite.iter().copied().reduce(f32::max),
+            mean: if finite.is_empty() {
+                None
+            } else {
+                Some(finite.iter().map(|&x| f64::from(x)).sum::<f64>() / finite.len() as f64)
             },
-        ],
-    });
+            nan_count: floats.clone().filter(|x| x.is_nan()).count(),
+            infinite_count: floats.filter(|x| x.is_infinite()).count(),
+        }
+    }
+}
 
-    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-        label: None,
-        bind_group_layouts: &[&bind_group_layout],
-        push_constant_ranges: &[],
-    });
+#[derive(Serialize)]
+pub struct TimingSummary {
+    #[serde(rename = "min_ns", serialize_with = "serialize_nanos")]
+    pub min: Duration,
+    #[serde(rename = "mean_ns", serialize_with = "serialize_nanos")]
+    pub mean: Duration,
+    #[serde(rename = "max_ns", serialize_with = "serialize_nanos")]
+    pub max: Duration,
+    #[serde(rename = "p50_ns", serialize_with = "serialize_nanos")]
+    pub p50: Duration,
+    #[serde(rename = "p95_ns", serialize_with = "serialize_nanos")]
+    pub p95: Duration,
+    #[serde(rename = "p99_ns", serialize_with = "serialize_nanos")]
+    pub p99: Duration,
+}
 
-    let compute_pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-        label: None,
-        layout: Some(&pipeline_layout),
-        module: &module,
-        entry_point: "main_cs",
-    });
+impl TimingSummary {
+    /// Summarizes `samples`, which must not be empty.
+    fn new(samples: &[Duration]) -> Self {
+        let mut sorted = samples.to_vec();
+        sorted.sort_unstable();
+        // Nearest-rank percentiles, so every reported time is one that was actually measured.
+        let percentile = |p: usize| sorted[((p * sorted.len() + 99) / 100).max(1) - 1];
+        Self {
+            min: sorted[0],
+            mean: samples.iter().sum::<Duration>() / samples.len() as u32,
+            max: sorted[sorted.len() - 1],
+            p50: percentile(50),
+            p95: percentile(95),
+            p99: percentile(99),
+        }
+    }
+}
 
-    let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-        label: None,
-        size: src.len() as wgpu::BufferAddress,
-        // Can be read to the CPU, and can be copied from the shader's storage buffer
-        usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
-        mapped_at_creation: false,
-    });
+/// The serializable parts of `wgpu::AdapterInfo`.
+#[derive(Serialize)]
+pub struct AdapterSummary {
+    pub name: String,
+    pub vendor: usize,
+    pub device: usize,
+    pub device_type: String,
+    pub backend: String,
+    /// The number of invocations in a subgroup (wave, warp). wgpu doesn't expose this on any
+    /// backend yet, not even where the underlying API does (e.g. Vulkan's
+    /// `VK_EXT_subgroup_size_control`), so it's always `None` for now.
+    pub subgroup_size: Option<u32>,
+    /// What picked the adapter: the option or environment variable, or `default`.
+    pub selected_by: &'static str,
+}
 
-    let storage_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-        label: Some("Collatz Conjecture Input"),
-        contents: &src,
-        usage: wgpu::BufferUsage::STORAGE
-            | wgpu::BufferUsage::COPY_DST
-            | wgpu::BufferUsage::COPY_SRC,
-    });
+impl AdapterSummary {
+    fn new(info: wgpu::AdapterInfo, selected_by: &'static str) -> Self {
+        Self {
+            name: info.name,
+            vendor: info.vendor,
+            device: info.device,
+            device_type: format!("{:?}", info.device_type),
+            backend: format!("{:?}", info.backend),
+            subgroup_size: None,
+            selected_by,
+        }
+    }
+}
 
-    let timestamp_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-        label: Some("Timestamps buffer"),
-        size: 16,
-        usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
-        mapped_at_creation: false,
-    });
+/// Where the timestamps timing each dispatch are written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampGranularity {
+    /// Inside the compute pass, directly around the dispatch.
+    Pass,
+    /// On the encoder, around the whole compute pass. The times then include beginning and
+    /// ending the pass, so they're slightly longer than the dispatch itself.
+    Encoder,
+}
 
-    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-        label: None,
-        layout: &bind_group_layout,
-        entries: &[wgpu::BindGroupEntry {
-            binding: 0,
-            resource: storage_buffer.as_entire_binding(),
-        }],
-    });
+impl TimestampGranularity {
+    /// Picks the finest granularity `backend` supports.
+    ///
+    /// This version of wgpu has a single `TIMESTAMP_QUERY` feature covering timestamps both
+    /// inside and outside passes (later versions split out `TIMESTAMP_QUERY_INSIDE_PASSES`, which
+    /// should be checked here instead when upgrading), so support is decided by backend: Vulkan
+    /// and D3D12 can write timestamps anywhere in a command buffer, while Metal can only sample
+    /// them at pass boundaries.
+    fn new(backend: wgpu::Backend) -> Self {
+        match backend {
+            wgpu::Backend::Vulkan | wgpu::Backend::Dx12 => Self::Pass,
+            _ => Self::Encoder,
+        }
+    }
+}
 
-    let queries = device.create_query_set(&wgpu::QuerySetDescriptor {
-        count: 2,
-        ty: wgpu::QueryType::Timestamp,
-    });
+impl fmt::Display for TimestampGranularity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pass => f.write_str("inside the compute pass"),
+            Self::Encoder => f.write_str("around the compute pass"),
+        }
+    }
+}
 
-    let mut encoder =
-        device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+/// How the results are read back from the GPU.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Readback {
+    /// By mapping the storage buffer the shader works on, with no copy.
+    Mapped,
+    /// By copying the storage buffer to a separate buffer, and mapping that.
+    Copied,
+}
 
-    {
-        let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
-        cpass.set_bind_group(0, &bind_group, &[]);
-        cpass.set_pipeline(&compute_pipeline);
-        cpass.write_timestamp(&queries, 0);
-        cpass.dispatch(src_range.len() as u32 / 64, 1, 1);
-        cpass.write_timestamp(&queries, 1);
-    }
-
-    encoder.copy_buffer_to_buffer(
-        &storage_buffer,
-        0,
-        &readback_buffer,
-        0,
-        src.len() as wgpu::BufferAddress,
-    );
-    encoder.resolve_query_set(&queries, 0..2, &timestamp_buffer, 0);
-
-    queue.submit(Some(encoder.finish()));
-    let buffer_slice = readback_buffer.slice(..);
-    let timestamp_slice = timestamp_buffer.slice(..);
-    let timestamp_future = timestamp_slice.map_async(wgpu::MapMode::Read);
-    let buffer_future = buffer_slice.map_async(wgpu::MapMode::Read);
-    device.poll(wgpu::Maintain::Wait);
-
-    if let (Ok(()), Ok(())) = join(buffer_future, timestamp_future).await {
-        let data = buffer_slice.get_mapped_range();
-        let timing_data = timestamp_slice.get_mapped_range();
-        let result = data
-            .chunks_exact(4)
-            .map(|b| u32::from_ne_bytes(b.try_into().unwrap()))
-            .collect::<Vec<_>>();
-        let timings = timing_data
-            .chunks_exact(8)
-            .map(|b| u64::from_ne_bytes(b.try_into().unwrap()))
-            .collect::<Vec<_>>();
-        drop(data);
-        readback_buffer.unmap();
-        drop(timing_data);
-        timestamp_buffer.unmap();
-        let mut max = 0;
-        for (src, out) in src_range.zip(result.iter().copied()) {
-            if out == u32::MAX {
-                println!("{}: overflowed", src);
-                break;
-            } else if out > max {
-                max = out;
-                // Should produce <https://oeis.org/A006877>
-                println!("{}: {}", src, out);
+impl Readback {
+    /// Maps the storage buffer directly wherever `features` allow it.
+    ///
+    /// Without `MAPPABLE_PRIMARY_BUFFERS`, wgpu only allows mapping buffers that are otherwise
+    /// just copied to, so the results have to go through a separate buffer.
+    fn new(features: wgpu::Features) -> Self {
+        if features.contains(wgpu::Features::MAPPABLE_PRIMARY_BUFFERS) {
+            Self::Mapped
+        } else {
+            Self::Copied
+        }
+    }
+}
+
+impl fmt::Display for Readback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Mapped => f.write_str("mapped the storage buffer directly"),
+            Self::Copied => f.write_str("copied through a readback buffer"),
+        }
+    }
+}
+
+fn serialize_nanos<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(duration.as_nanos() as u64)
+}
+
+/// Polls `device` until `future` completes, giving up after `timeout` if there is one.
+///
+/// On the web the browser makes progress on GPU work by itself, and there's neither a clock to
+/// time it out by nor a thread to sleep, so the timeout is ignored there.
+async fn wait_for<F: Future>(
+    device: &wgpu::Device,
+    future: F,
+    timeout: Option<Duration>,
+) -> Result<F::Output, RunnerError> {
+    let timeout = match timeout {
+        Some(timeout) if !cfg!(target_arch = "wasm32") => timeout,
+        _ => {
+            device.poll(wgpu::Maintain::Wait);
+            return Ok(future.await);
+        }
+    };
+    let deadline = Instant::now() + timeout;
+    futures::pin_mut!(future);
+    loop {
+        device.poll(wgpu::Maintain::Poll);
+        if let Some(output) = future.as_mut().now_or_never() {
+            return Ok(output);
+        }
+        if Instant::now() >= deadline {
+            return Err(RunnerError::Timeout(timeout));
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+}
+
+/// The number of workgroups a dispatch may use in each dimension.
+///
+/// wgpu doesn't expose the device's actual limit (`maxComputeWorkGroupCount` in Vulkan), so this
+/// is the minimum all backends guarantee.
+const MAX_WORKGROUPS_PER_DIMENSION: u32 = 65535;
+
+#[derive(Debug)]
+pub enum RunnerError {
+    /// No adapter compatible with the requested options was found.
+    NoAdapter,
+    /// No adapter's name contains the one asked for.
+    NoAdapterNamed(String),
+    /// `--software` found no adapter running on the CPU.
+    NoSoftwareAdapter,
+    RequestDevice(wgpu::RequestDeviceError),
+    /// The shader failed to build. The compiler reports the reasons itself.
+    BuildFailed,
+    InvalidSpirv(ParseState),
+    /// Reading a `--spv-file` failed.
+    SpvLoadFailed(String),
+    /// The shader has no compute entry point with the given name.
+    MissingEntryPoint(String),
+    /// wgpu rejected the shader module, with the full validation error.
+    ShaderModule(String),
+    /// The input needs more workgroups than can be dispatched.
+    TooManyWorkgroups {
+        workgroups: usize,
+        limit: u32,
+    },
+    /// `--clear-range` isn't within the input.
+    ClearOutOfBounds {
+        range: Range<u32>,
+        len: usize,
+    },
+    /// The shader samples a texture, but no `--texture` was given.
+    MissingTexture,
+    /// The shader samples a different number of textures than were given.
+    TextureCount {
+        expected: usize,
+        given: usize,
+    },
+    /// The shader needs features the device doesn't support.
+    MissingFeatures(wgpu::Features),
+    /// The shader declares a capability that needs a feature the device doesn't support.
+    MissingCapability {
+        capability: Capability,
+        feature: wgpu::Features,
+    },
+    /// The shader declares a capability for 8-bit types in buffers, e.g. a `&[u8]` storage
+    /// buffer. The device features those need are missing from wgpu 0.7, which the runner is
+    /// built on, so it has no way to enable them.
+    UnsupportedCapability(Capability),
+    TextureLoadFailed(String),
+    /// An `--input` or `--output` can't be bound at `binding`.
+    BufferBinding {
+        binding: u32,
+        reason: &'static str,
+    },
+    BufferLoadFailed {
+        binding: u32,
+        error: String,
+    },
+    /// An `--input` file doesn't have the size of the buffer reflected at its binding: `size`
+    /// bytes, followed by any number of `element_size` byte elements for runtime-sized arrays.
+    BufferSize {
+        binding: u32,
+        len: usize,
+        size: u64,
+        element_size: Option<u64>,
+    },
+    /// The buffer bound at `binding` is `provided` bytes, smaller than the `required` size of the
+    /// type the shader declares there, not counting a trailing runtime-sized array.
+    BindingTooSmall {
+        binding: u32,
+        required: u64,
+        provided: u64,
+    },
+    /// The shader's runtime-sized array at `binding` has elements `expected` bytes apart, but
+    /// the host uploads them `actual` bytes apart, e.g. a host struct with different packing.
+    ElementStride {
+        binding: u32,
+        expected: u64,
+        actual: u64,
+    },
+    /// The shader declares `used` bytes of workgroup shared memory, more than the `limit` of
+    /// `--max-workgroup-storage`.
+    WorkgroupStorageTooLarge {
+        used: u64,
+        limit: u64,
+    },
+    /// The `--expect` image couldn't be compared against.
+    ExpectedImage(String),
+    /// The output differs from the `--expect` image in this many pixels, marked in `diff`.
+    ImageMismatch {
+        mismatched: usize,
+        diff: PathBuf,
+    },
+    /// A `--repeat-until-diff` run gave different results than the first run, in this many
+    /// elements.
+    OutputChanged {
+        run: u32,
+        elements: usize,
+    },
+    /// wgpu rejected a resource created during the named `stage` of setting up a dispatch, e.g.
+    /// the `compute pipeline`, with this validation error.
+    Validation {
+        stage: &'static str,
+        message: String,
+    },
+    /// The `--output-mmap` file couldn't be created, sized, mapped or written.
+    OutputFile(String),
+    /// `--compare-passthrough` on a backend that can't pass modules through unvalidated.
+    PassthroughUnsupported(wgpu::Backend),
+    /// `--compare-passthrough` gave different results with naga than passed through, in this many
+    /// elements.
+    PassthroughDiffers {
+        elements: usize,
+    },
+    /// Mapping the readback buffers failed.
+    MapFailed,
+    /// The GPU work didn't finish within `--timeout`.
+    Timeout(Duration),
+    /// The device failed with `error` while setting up or running the shader, related back to
+    /// the `locations` in the Rust source of the entry point, then of any functions the error
+    /// names, by the module's line info.
+    InShader {
+        error: Box<RunnerError>,
+        locations: Vec<(String, SourceLocation)>,
+    },
+}
+
+impl fmt::Display for RunnerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunnerError::NoAdapter => f.write_str("Failed to find an appropriate adapter"),
+            RunnerError::NoAdapterNamed(name) => {
+                write!(f, "Failed to find an adapter named like `{}`", name)
+            }
+            RunnerError::NoSoftwareAdapter => f.write_str(
+                "Failed to find a software adapter: install lavapipe (e.g. `mesa-vulkan-drivers`) \
+                 for Vulkan, or use WARP on Windows with `--backends dx12`",
+            ),
+            RunnerError::RequestDevice(err) => write!(f, "Failed to create device: {}", err),
+            RunnerError::BuildFailed => f.write_str("Failed to build the shader"),
+            RunnerError::InvalidSpirv(err) => write!(f, "Shader is not valid SPIR-V: {:?}", err),
+            RunnerError::SpvLoadFailed(err) => write!(f, "Failed to load SPIR-V: {}", err),
+            RunnerError::MissingEntryPoint(name) => {
+                write!(f, "Shader has no compute entry point named `{}`", name)
+            }
+            RunnerError::ShaderModule(err) => {
+                write!(f, "wgpu rejected the shader module: {}", err)
+            }
+            RunnerError::TooManyWorkgroups { workgroups, limit } => write!(
+                f,
+                "Input needs {} workgroups, but at most {} can be dispatched",
+                workgroups, limit
+            ),
+            RunnerError::MissingTexture => {
+                f.write_str("The shader samples a texture, but no --texture was given")
+            }
+            RunnerError::ClearOutOfBounds { range, len } => write!(
+                f,
+                "Can't clear {:?}, the input only has {} elements",
+                range, len
+            ),
+            RunnerError::TextureCount { expected, given } => write!(
+                f,
+                "The shader samples {} textures, but {} were given",
+                expected, given
+            ),
+            RunnerError::MissingFeatures(features) => write!(
+                f,
+                "The device doesn't support {}",
+                feature_names(*features).join(", ")
+            ),
+            RunnerError::MissingCapability {
+                capability,
+                feature,
+            } => write!(
+                f,
+                "The shader needs {:?}, but the device doesn't support {:?}",
+                capability, feature
+            ),
+            RunnerError::UnsupportedCapability(capability) => write!(
+                f,
+                "The shader needs {:?}, which wgpu 0.7 can't enable{}",
+                capability,
+                if is_8bit_storage(*capability) {
+                    ", read bytes packed into u32s instead"
+                } else {
+                    ""
+                }
+            ),
+            RunnerError::TextureLoadFailed(err) => write!(f, "Failed to load texture: {}", err),
+            RunnerError::BufferBinding { binding, reason } => {
+                write!(
+                    f,
+                    "Can't bind a buffer file at binding {}: it {}",
+                    binding, reason
+                )
+            }
+            RunnerError::BufferLoadFailed { binding, error } => {
+                write!(
+                    f,
+                    "Failed to load the buffer for binding {}: {}",
+                    binding, error
+                )
+            }
+            RunnerError::BufferSize {
+                binding,
+                len,
+                size,
+                element_size: Some(element_size),
+            } => write!(
+                f,
+                "Binding {} takes {} bytes plus a multiple of {}, but its --input is {} bytes",
+                binding, size, element_size, len
+            ),
+            RunnerError::BufferSize {
+                binding,
+                len,
+                size,
+                element_size: None,
+            } => write!(
+                f,
+                "Binding {} takes {} bytes, but its --input is {} bytes",
+                binding, size, len
+            ),
+            RunnerError::BindingTooSmall {
+                binding,
+                required,
+                provided,
+            } => write!(
+                f,
+                "The shader needs at least {} bytes at binding {}, but the buffer bound there is \
+                 only {} bytes",
+                required, binding, provided
+            ),
+            RunnerError::ElementStride {
+                binding,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Binding {} has a stride of {} bytes per element, but the host uploads {} bytes \
+                 per element",
+                binding, expected, actual
+            ),
+            RunnerError::WorkgroupStorageTooLarge { used, limit } => write!(
+                f,
+                "The shader declares {} bytes of workgroup storage, more than the {} bytes \
+                 allowed, see --max-workgroup-storage",
+                used, limit
+            ),
+            RunnerError::ExpectedImage(err) => {
+                write!(f, "Failed to compare against the expected image: {}", err)
+            }
+            RunnerError::ImageMismatch { mismatched, diff } => write!(
+                f,
+                "{} pixels differ from the expected image, see {}",
+                mismatched,
+                diff.display()
+            ),
+            RunnerError::OutputChanged { run, elements } => write!(
+                f,
+                "Run {} gave different results than the first run in {} elements",
+                run, elements
+            ),
+            RunnerError::Validation { stage, message } => {
+                write!(f, "Failed to create the {}: {}", stage, message)
+            }
+            RunnerError::OutputFile(err) => write!(f, "Failed to write the results: {}", err),
+            RunnerError::PassthroughUnsupported(backend) => write!(
+                f,
+                "--compare-passthrough needs Vulkan, passthrough isn't supported on {:?}",
+                backend
+            ),
+            RunnerError::PassthroughDiffers { elements } => write!(
+                f,
+                "Passthrough gave different results than naga in {} elements",
+                elements
+            ),
+            RunnerError::MapFailed => f.write_str("Failed to map the readback buffers"),
+            RunnerError::Timeout(timeout) => {
+                write!(f, "GPU work didn't finish within {:?}", timeout)
+            }
+            RunnerError::InShader { error, locations } => {
+                write!(f, "{}", error)?;
+                for (name, location) in locations {
+                    write!(f, "\n  in `{}` at {}", name, location)?;
+                }
+                Ok(())
             }
         }
-        println!(
-            "Took: {:?}",
-            Duration::from_nanos(
-                ((timings[1] - timings[0]) as f64 * f64::from(timestamp_period)) as u64
-            )
+    }
+}
+
+impl Error for RunnerError {}
+
+/// The entry point of the example compute shader that scans for the running maxima within each
+/// workgroup, rather than leaving that to the host.
+const SCAN_ENTRY_POINT: &str = "main_scan_cs";
+
+fn serialize_nanos_slice<S: Serializer>(
+    durations: &[Duration],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(durations.iter().map(|duration| duration.as_nanos() as u64))
+}
+
+/// `--sanity`: warns about the buffers the dispatch left just as they were uploaded, which usually
+/// means the shader never wrote to them, e.g. running the wrong entry point, dispatching no
+/// workgroups, or declaring its buffers at other bindings than the runner binds them.
+fn warn_if_unchanged(options: &Options, src: &[u32], output: &ComputeOutput) {
+    let hint = "check the entry point, the dispatch size and the bindings";
+    // Results only in the `--output-mmap` file, or not read back at all, can't be compared.
+    if options.output_mmap.is_none() && !options.count_only {
+        let mut uploaded = src.to_vec();
+        if let Some(value) = options.clear {
+            // The dispatch has already checked the range is within the input.
+            let range = options.clear_range.clone().unwrap_or(0..src.len() as u32);
+            uploaded[range.start as usize..range.end as usize].fill(value);
+        }
+        if output.result == uploaded {
+            eprintln!(
+                "Warning: the dispatch left binding 0 as it was uploaded, so the shader may not \
+                 have written to it ({})",
+                hint
+            );
+        }
+    }
+    if output.count == Some(0) {
+        eprintln!(
+            "Warning: the counter at binding {} is still 0, so the shader may not have counted \
+             anything ({})",
+            COUNT_BINDING, hint
         );
     }
+    for (binding, contents) in &output.outputs {
+        let zeroed = !options.input.iter().any(|file| file.binding == *binding)
+            && !options
+                .append
+                .iter()
+                .any(|append| append.binding == *binding);
+        if zeroed && contents.iter().all(|&byte| byte == 0) {
+            eprintln!(
+                "Warning: the dispatch left binding {} zeroed, so the shader may not have written \
+                 to it ({})",
+                binding, hint
+            );
+        }
+    }
+}
+
+/// Finishes the running maxima from [`SCAN_ENTRY_POINT`], which only scans within workgroups, by
+/// carrying the maximum of each workgroup into the ones after it. That's one comparison per
+/// element, without the Collatz steps the GPU already did.
+fn carry_workgroup_maxima(result: &mut [u32], local_size: [u32; 3]) {
+    let mut carry = 0;
+    for workgroup in result.chunks_mut(local_size[0].max(1) as usize) {
+        for value in workgroup.iter_mut() {
+            *value = (*value).max(carry);
+        }
+        carry = workgroup.last().copied().unwrap_or(carry);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn millis(samples: &[u64]) -> Vec<Duration> {
+        samples.iter().copied().map(Duration::from_millis).collect()
+    }
+
+    #[test]
+    fn timing_summary_of_one_sample() {
+        let summary = TimingSummary::new(&millis(&[5]));
+        let five = Duration::from_millis(5);
+        assert_eq!([summary.min, summary.mean, summary.max], [five, five, five]);
+        assert_eq!([summary.p50, summary.p95, summary.p99], [five, five, five]);
+    }
+
+    #[test]
+    fn timing_summary_of_two_samples() {
+        let summary = TimingSummary::new(&millis(&[20, 10]));
+        assert_eq!(summary.min, Duration::from_millis(10));
+        assert_eq!(summary.mean, Duration::from_millis(15));
+        assert_eq!(summary.max, Duration::from_millis(20));
+        // The median of two is the lower one by nearest rank, never an average of the two.
+        assert_eq!(summary.p50, Duration::from_millis(10));
+        assert_eq!(summary.p95, Duration::from_millis(20));
+        assert_eq!(summary.p99, Duration::from_millis(20));
+    }
+
+    #[test]
+    fn timing_summary_of_three_samples() {
+        let summary = TimingSummary::new(&millis(&[3, 1, 2]));
+        assert_eq!(summary.p50, Duration::from_millis(2));
+        assert_eq!(summary.p95, Duration::from_millis(3));
+    }
+
+    #[test]
+    fn timing_summary_of_ten_samples() {
+        let summary = TimingSummary::new(&millis(&[7, 3, 10, 1, 9, 2, 8, 4, 6, 5]));
+        assert_eq!(summary.min, Duration::from_millis(1));
+        assert_eq!(summary.mean, Duration::from_micros(5500));
+        assert_eq!(summary.max, Duration::from_millis(10));
+        assert_eq!(summary.p50, Duration::from_millis(5));
+        assert_eq!(summary.p95, Duration::from_millis(10));
+        assert_eq!(summary.p99, Duration::from_millis(10));
+    }
+
+    #[test]
+    fn result_hash_is_pinned() {
+        // Changing these breaks comparing hashes with those of earlier runs.
+        assert_eq!(result_hash(&[]), "cbf29ce484222325");
+        assert_eq!(result_hash(&[0x61]), "ac804b820e4fe984");
+        assert_eq!(result_hash(&[1, 2, 0xdead_beef]), "6ac48c2c1749eba8");
+    }
 }
@@ -0,0 +1,1685 @@
+//! [`ComputeContext`]: the adapter, device and queue compute shaders are dispatched on, and the
+//! buffers, bindings and readback of a dispatch.
+
+use super::{
+    result_hash, wait_for, warmup_count, AdapterSummary, Appended, ComputeOutput, DebugRecords,
+    FloatSummary, Readback, RunnerError, TimestampGranularity, TimingSummary, ValuesSummary,
+    COUNT_BINDING, DEBUG_BINDING, DEFAULT_MAX_WORKGROUP_STORAGE, MAX_WORKGROUPS_PER_DIMENSION,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::output_file::OutputFile;
+use crate::reflect::{
+    used_capabilities, ArrayLength, Binding, BindingKind, BufferAccess, Reflection,
+};
+use crate::texture::SampledTexture;
+use crate::trace::Trace;
+use crate::{Endian, Filter, Options, OutputType, Verbosity};
+use futures::future::{join, join_all, ready, try_join_all, BoxFuture, FutureExt};
+use rspirv::spirv::{Capability, ExecutionModel};
+use std::convert::{TryFrom, TryInto};
+use std::num::{NonZeroU32, NonZeroU64};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use wgpu::util::DeviceExt;
+
+/// Fills the input past its end, up to a whole number of workgroups. Zero isn't part of any
+/// Collatz sequence, so the shader gives up on it right away.
+const PADDING_SENTINEL: u32 = 0;
+
+/// The size of the elements uploaded to the main storage buffer, which are always `u32`s.
+const ELEMENT_STRIDE: u64 = 4;
+
+/// The workgroup size assumed for shaders it can't be reflected from.
+const DEFAULT_LOCAL_SIZE: [u32; 3] = [64, 1, 1];
+
+/// Relates `err` back to the Rust source of the entry point it happened running, and of the
+/// functions its message names, if the device raised it and the module has line info.
+///
+/// Neither this version of wgpu nor the drivers under it say which instruction failed, so the
+/// start of the entry point is as close as it gets, unless it's a validation error naming a
+/// function.
+fn with_source_locations(
+    err: RunnerError,
+    shader_binary: &wgpu::ShaderModuleDescriptor<'_>,
+    entry_point: &str,
+) -> RunnerError {
+    let message = match &err {
+        RunnerError::ShaderModule(message) | RunnerError::Validation { message, .. } => {
+            Some(message.as_str())
+        }
+        RunnerError::MapFailed | RunnerError::Timeout(_) => None,
+        _ => return err,
+    };
+    let reflection = match reflect(shader_binary) {
+        Ok(Some(reflection)) => reflection,
+        _ => return err,
+    };
+    let mut locations = reflection
+        .entry_point(entry_point, ExecutionModel::GLCompute)
+        .and_then(|entry_point| Some((entry_point.name.clone(), entry_point.source.clone()?)))
+        .into_iter()
+        .collect::<Vec<_>>();
+    if let Some(message) = message {
+        for (name, location) in &reflection.function_sources {
+            if mentions(message, name) && !locations.iter().any(|(other, _)| other == name) {
+                locations.push((name.clone(), location.clone()));
+            }
+        }
+    }
+    if locations.is_empty() {
+        err
+    } else {
+        RunnerError::InShader {
+            error: Box::new(err),
+            locations,
+        }
+    }
+}
+
+/// Whether `message` mentions `name` as a whole, rather than as part of a longer identifier or
+/// path.
+fn mentions(message: &str, name: &str) -> bool {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_' || c == ':';
+    message.match_indices(name).any(|(start, _)| {
+        !message[..start].ends_with(is_ident)
+            && !message[start + name.len()..].starts_with(is_ident)
+    })
+}
+
+/// Reflects `shader_binary`, unless it's WGSL.
+pub(super) fn reflect(
+    shader_binary: &wgpu::ShaderModuleDescriptor<'_>,
+) -> Result<Option<Reflection>, RunnerError> {
+    match &shader_binary.source {
+        wgpu::ShaderSource::SpirV(spirv) => Reflection::new(spirv)
+            .map(Some)
+            .map_err(RunnerError::InvalidSpirv),
+        wgpu::ShaderSource::Wgsl(_) => Ok(None),
+    }
+}
+
+/// The device feature a shader declaring `capability` needs, if there is one.
+///
+/// Capabilities without a feature are either supported everywhere, or not at all, in which case
+/// [`check_capabilities`] or creating the shader module reports them.
+fn capability_feature(capability: Capability) -> Option<wgpu::Features> {
+    match capability {
+        Capability::Float64 => Some(wgpu::Features::SHADER_FLOAT64),
+        Capability::RuntimeDescriptorArray => Some(wgpu::Features::UNSIZED_BINDING_ARRAY),
+        Capability::SampledImageArrayDynamicIndexing => {
+            Some(wgpu::Features::SAMPLED_TEXTURE_ARRAY_DYNAMIC_INDEXING)
+        }
+        Capability::SampledImageArrayNonUniformIndexing => {
+            Some(wgpu::Features::SAMPLED_TEXTURE_ARRAY_NON_UNIFORM_INDEXING)
+        }
+        _ => None,
+    }
+}
+
+/// Whether `capability` is for 8-bit types in buffers, reported as
+/// [`RunnerError::UnsupportedCapability`].
+pub(super) fn is_8bit_storage(capability: Capability) -> bool {
+    matches!(
+        capability,
+        Capability::StorageBuffer8BitAccess
+            | Capability::UniformAndStorageBuffer8BitAccess
+            | Capability::StoragePushConstant8
+    )
+}
+
+/// Checks the device has the features every capability `spirv` uses needs, so that a shader
+/// needing e.g. `Float64` fails saying so, rather than however wgpu copes with the module.
+fn check_capabilities(features: wgpu::Features, spirv: &[u32]) -> Result<(), RunnerError> {
+    let capabilities = used_capabilities(spirv).map_err(RunnerError::InvalidSpirv)?;
+    for capability in capabilities {
+        if is_8bit_storage(capability) {
+            return Err(RunnerError::UnsupportedCapability(capability));
+        }
+        if let Some(feature) = capability_feature(capability) {
+            if !features.contains(feature) {
+                return Err(RunnerError::MissingCapability {
+                    capability,
+                    feature,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The workgroup size of the entry point, or [`DEFAULT_LOCAL_SIZE`] if it can't be reflected.
+pub(super) fn local_size(reflection: Option<&Reflection>, entry_point: &str) -> [u32; 3] {
+    reflection
+        .and_then(|reflection| reflection.entry_point(entry_point, ExecutionModel::GLCompute))
+        .and_then(|entry_point| entry_point.local_size)
+        .unwrap_or(DEFAULT_LOCAL_SIZE)
+}
+
+/// The number of elements uploaded for a `len` long input.
+///
+/// The input is padded out to whole workgroups with [`PADDING_SENTINEL`], so the last workgroup's
+/// extra invocations have something to work on. Their results are trimmed off again on readback.
+pub(super) fn padded_len(
+    options: &Options,
+    len: usize,
+    workgroups: [u32; 3],
+    local_size: [u32; 3],
+) -> usize {
+    if options.dispatch_dims.is_some() {
+        len
+    } else {
+        workgroups[0] as usize * local_size[0] as usize
+    }
+}
+
+/// The number of workgroups to dispatch in each dimension for a shader with the given workgroup
+/// size.
+///
+/// Each dimension is rounded up to whole workgroups. With `--dispatch-dims` shaders have to skip
+/// the invocations past the edges of the grid themselves, otherwise one invocation is dispatched
+/// per element of the `len` long input, which [`ComputeContext::dispatch`] pads to match.
+pub(super) fn workgroup_counts(
+    options: &Options,
+    len: usize,
+    local_size: [u32; 3],
+) -> Result<[u32; 3], RunnerError> {
+    let local_len = local_size[0].max(1) as usize;
+    let mut workgroups = [(len + local_len - 1) / local_len, 1, 1];
+    if let Some(dims) = options.dispatch_dims {
+        for (count, (&dim, &size)) in workgroups.iter_mut().zip(dims.iter().zip(&local_size)) {
+            let size = u64::from(size.max(1));
+            *count = ((u64::from(dim) + size - 1) / size) as usize;
+        }
+    }
+    let mut counts = [0; 3];
+    for (count, &workgroups) in counts.iter_mut().zip(&workgroups) {
+        *count = u32::try_from(workgroups)
+            .ok()
+            .filter(|&count| count <= MAX_WORKGROUPS_PER_DIMENSION)
+            .ok_or(RunnerError::TooManyWorkgroups {
+                workgroups,
+                limit: MAX_WORKGROUPS_PER_DIMENSION,
+            })?;
+    }
+    Ok(counts)
+}
+
+/// The size of the chunks to read a storage buffer of `buffer_size` bytes back in, if it's larger
+/// than `--max-map-size`. Copies have to be a multiple of `COPY_BUFFER_ALIGNMENT` in size, so
+/// chunks are rounded down to that, and `OptionsBuilder::build` checks they're not rounded to 0.
+fn readback_chunk_size(options: &Options, buffer_size: u64) -> Option<wgpu::BufferAddress> {
+    let max_map_size = options.max_map_size.filter(|&max| max < buffer_size)?;
+    Some(max_map_size - max_map_size % wgpu::COPY_BUFFER_ALIGNMENT)
+}
+
+/// `bytes` as the `u32`s the storage buffer holds.
+fn result_words(bytes: &[u8]) -> Vec<u32> {
+    bytes
+        .chunks_exact(4)
+        .map(|b| u32::from_ne_bytes(b.try_into().unwrap()))
+        .collect()
+}
+
+/// The results in `data`, read back from a storage buffer padded past its first `logical_size`
+/// bytes, without the padding.
+fn logical_result(data: &[u8], logical_size: usize) -> Vec<u32> {
+    result_words(&data[..logical_size])
+}
+
+/// `size` rounded up to a multiple of `wgpu::COPY_BUFFER_ALIGNMENT`.
+pub(super) fn aligned_buffer_size(size: usize) -> wgpu::BufferAddress {
+    let align = wgpu::COPY_BUFFER_ALIGNMENT;
+    (size as wgpu::BufferAddress + align - 1) / align * align
+}
+
+/// The `min_binding_size` of the buffer bound at `set`/`binding`: `None` for runtime-sized arrays,
+/// which can be bound with any size, and the reflected size of the bound type otherwise.
+fn min_binding_size(reflection: Option<&Reflection>, set: u32, binding: u32) -> Option<NonZeroU64> {
+    let binding = reflection?.binding(set, binding)?;
+    if binding.runtime_array {
+        None
+    } else {
+        binding.size.and_then(NonZeroU64::new)
+    }
+}
+
+/// The type the buffer at `set`/`binding` has to be bound as, following how the shader accesses
+/// it. Buffers that can't be reflected, e.g. those of WGSL shaders, are bound read-write.
+fn buffer_binding_type(
+    reflection: Option<&Reflection>,
+    set: u32,
+    binding: u32,
+) -> wgpu::BufferBindingType {
+    let access = reflection
+        .and_then(|reflection| reflection.binding(set, binding))
+        .and_then(|binding| binding.access);
+    match access {
+        Some(BufferAccess::Uniform) => wgpu::BufferBindingType::Uniform,
+        Some(BufferAccess::ReadOnly) => wgpu::BufferBindingType::Storage { read_only: true },
+        Some(BufferAccess::ReadWrite) | None => {
+            wgpu::BufferBindingType::Storage { read_only: false }
+        }
+    }
+}
+
+/// The usage a buffer needs to be bound as `ty`.
+fn binding_usage(ty: wgpu::BufferBindingType) -> wgpu::BufferUsage {
+    match ty {
+        wgpu::BufferBindingType::Uniform => wgpu::BufferUsage::UNIFORM,
+        wgpu::BufferBindingType::Storage { .. } => wgpu::BufferUsage::STORAGE,
+    }
+}
+
+pub(super) fn feature_names(features: wgpu::Features) -> Vec<String> {
+    (0..64)
+        .filter_map(|bit| wgpu::Features::from_bits(1 << bit))
+        .filter(|&feature| features.contains(feature))
+        .map(|feature| format!("{:?}", feature))
+        .collect()
+}
+
+/// The contents of the extra storage buffers to bind: the `--input` files, and zeroes for the
+/// `--append` buffers and the `--output` bindings that aren't also inputs.
+fn extra_buffer_contents(
+    options: &Options,
+    reflection: Option<&Reflection>,
+    invocations: u64,
+) -> Result<Vec<(u32, Vec<u8>)>, RunnerError> {
+    let mut buffers: Vec<(u32, Vec<u8>)> = Vec::new();
+    let debug = debug_binding(reflection)?;
+    if let Some(binding) = debug {
+        let size = invocations as usize * debug_record_words(binding) * 4;
+        buffers.push((DEBUG_BINDING, vec![0; size]));
+    }
+    // A binding that's already taken, by the debug records rather than the options if it's theirs.
+    let taken = |binding: u32, reason: &'static str| RunnerError::BufferBinding {
+        binding,
+        reason: if debug.is_some() && binding == DEBUG_BINDING {
+            "is reserved for the shader's debug records"
+        } else {
+            reason
+        },
+    };
+    for file in &options.input {
+        let binding = extra_buffer_binding(reflection, file.binding)?;
+        if buffers.iter().any(|(other, _)| *other == file.binding) {
+            return Err(taken(file.binding, "is given more than one --input"));
+        }
+        let mut contents =
+            std::fs::read(&file.path).map_err(|err| RunnerError::BufferLoadFailed {
+                binding: file.binding,
+                error: format!("{}: {}", file.path.display(), err),
+            })?;
+        if options.input_endian != Endian::Native && contents.len() % 4 != 0 {
+            return Err(RunnerError::BufferLoadFailed {
+                binding: file.binding,
+                error: format!(
+                    "{} is {} bytes, which isn't a whole number of u32s to byte swap",
+                    file.path.display(),
+                    contents.len()
+                ),
+            });
+        }
+        swap_words(options.input_endian, &mut contents);
+        check_buffer_size(binding, contents.len())?;
+        buffers.push((file.binding, contents));
+    }
+    for append in &options.append {
+        let binding = extra_buffer_binding(reflection, append.binding)?;
+        if buffers.iter().any(|(other, _)| *other == append.binding) {
+            return Err(taken(
+                append.binding,
+                "is given more than one --input or --append",
+            ));
+        }
+        if !binding.runtime_array {
+            return Err(RunnerError::BufferBinding {
+                binding: append.binding,
+                reason: "isn't runtime-sized, so there's nothing to --append to",
+            });
+        }
+        let (header, element_size) = append_layout(binding);
+        buffers.push((
+            append.binding,
+            vec![0; header + append.capacity as usize * element_size],
+        ));
+    }
+    if options.count_only {
+        extra_buffer_binding(reflection, COUNT_BINDING)?;
+        if buffers.iter().any(|(other, _)| *other == COUNT_BINDING) {
+            return Err(RunnerError::BufferBinding {
+                binding: COUNT_BINDING,
+                reason: "holds the --count-only counter, so can't be given an --input or --append",
+            });
+        }
+        buffers.push((COUNT_BINDING, vec![0; 4]));
+    }
+    for file in &options.output {
+        if file.binding == 0 || buffers.iter().any(|(other, _)| *other == file.binding) {
+            continue;
+        }
+        let binding = extra_buffer_binding(reflection, file.binding)?;
+        match binding.size {
+            Some(size) if !binding.runtime_array => {
+                buffers.push((file.binding, vec![0; size as usize]))
+            }
+            _ => {
+                return Err(RunnerError::BufferBinding {
+                    binding: file.binding,
+                    reason: "is runtime-sized, so it needs an --input to size it",
+                })
+            }
+        }
+    }
+    Ok(buffers)
+}
+
+/// The debug buffer the shader declares at [`DEBUG_BINDING`], if any.
+fn debug_binding(reflection: Option<&Reflection>) -> Result<Option<&Binding>, RunnerError> {
+    let binding = match reflection.and_then(|reflection| reflection.binding(0, DEBUG_BINDING)) {
+        Some(binding) if binding.kind == BindingKind::Buffer => binding,
+        _ => return Ok(None),
+    };
+    if !binding.runtime_array {
+        return Err(RunnerError::BufferBinding {
+            binding: DEBUG_BINDING,
+            reason: "is where debug records go, but isn't runtime-sized to hold one per invocation",
+        });
+    }
+    Ok(Some(binding))
+}
+
+/// The number of `u32`s in each record of the debug buffer bound at `binding`, one unless the
+/// records are arrays, e.g. `&mut [[u32; 4]]`.
+fn debug_record_words(binding: &Binding) -> usize {
+    binding
+        .element_size
+        .map_or(1, |size| (size as usize / 4).max(1))
+}
+
+/// The offset of the elements in an `--append` buffer bound at `binding`, after its cursor, and
+/// the size of each element. Assumes `u32` elements if the stride can't be reflected.
+fn append_layout(binding: &Binding) -> (usize, usize) {
+    let header = binding.size.map_or(4, |size| size.max(4) as usize);
+    let element_size = binding.element_size.map_or(4, |size| size as usize);
+    (header, element_size)
+}
+
+/// Converts the `u32`s in `bytes` between `endian` and the host's byte order, in which the GPU
+/// sees them. Converting is the same both ways, so this is used for `--input` and `--output`
+/// files alike. Any bytes after the last whole `u32` are left as they are.
+pub(super) fn swap_words(endian: Endian, bytes: &mut [u8]) {
+    let swap = match endian {
+        Endian::Le => cfg!(target_endian = "big"),
+        Endian::Be => cfg!(target_endian = "little"),
+        Endian::Native => false,
+    };
+    if swap {
+        for word in bytes.chunks_exact_mut(4) {
+            word.reverse();
+        }
+    }
+}
+
+/// The reflected storage buffer an `--input` or `--output` file is bound to.
+fn extra_buffer_binding(
+    reflection: Option<&Reflection>,
+    binding: u32,
+) -> Result<&Binding, RunnerError> {
+    let reason = if binding == 0 {
+        "holds the input range"
+    } else {
+        match reflection {
+            None => "can't be checked for WGSL shaders",
+            Some(reflection) => match reflection.binding(0, binding) {
+                Some(reflected)
+                    if reflected.kind == BindingKind::Buffer && reflected.array.is_none() =>
+                {
+                    return Ok(reflected)
+                }
+                _ => "isn't a storage buffer of the shader",
+            },
+        }
+    };
+    Err(RunnerError::BufferBinding { binding, reason })
+}
+
+/// Checks an `--input` of `len` bytes has the reflected size of `binding`. Bindings whose size
+/// can't be reflected take any size.
+fn check_buffer_size(binding: &Binding, len: usize) -> Result<(), RunnerError> {
+    let size = match binding.size {
+        Some(size) => size,
+        None => return Ok(()),
+    };
+    let element_size = binding
+        .element_size
+        .filter(|&element_size| element_size > 0);
+    let fits = match (binding.runtime_array, element_size) {
+        (false, _) => len as u64 == size,
+        (true, Some(element_size)) => len as u64 >= size && (len as u64 - size) % element_size == 0,
+        (true, None) => len as u64 >= size,
+    };
+    if fits {
+        Ok(())
+    } else {
+        Err(RunnerError::BufferSize {
+            binding: binding.binding,
+            len,
+            size,
+            element_size,
+        })
+    }
+}
+
+/// Checks the buffer of `provided` bytes bound at `set`/`binding` is at least as large as the
+/// shader's reflected minimum, the `min_binding_size` it's bound with (or the part before a
+/// runtime-sized array), rather than leaving wgpu to panic on the bind group, or the driver to
+/// read past the end of the buffer.
+fn check_binding_size(
+    reflection: Option<&Reflection>,
+    set: u32,
+    binding: u32,
+    provided: u64,
+) -> Result<(), RunnerError> {
+    let required = reflection
+        .and_then(|reflection| reflection.binding(set, binding))
+        .and_then(|binding| binding.size);
+    match required {
+        Some(required) if provided < required => Err(RunnerError::BindingTooSmall {
+            binding,
+            required,
+            provided,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Checks the shader's runtime-sized array at `binding` has elements `stride` bytes apart, as the
+/// host lays them out. Bindings without a runtime-sized array, or that can't be reflected, pass.
+fn check_element_stride(
+    reflection: Option<&Reflection>,
+    binding: u32,
+    stride: u64,
+) -> Result<(), RunnerError> {
+    let expected = reflection
+        .and_then(|reflection| reflection.binding(0, binding))
+        .filter(|binding| binding.runtime_array)
+        .and_then(|binding| binding.element_size);
+    match expected {
+        Some(expected) if expected != stride => Err(RunnerError::ElementStride {
+            binding,
+            expected,
+            actual: stride,
+        }),
+        _ => Ok(()),
+    }
+}
+
+pub(super) fn max_workgroup_storage(options: &Options) -> u64 {
+    options
+        .max_workgroup_storage
+        .unwrap_or(DEFAULT_MAX_WORKGROUP_STORAGE)
+}
+
+/// Checks the shader's workgroup shared memory fits in `--max-workgroup-storage`, rather than
+/// leaving the driver to fail pipeline creation over it without saying why.
+fn check_workgroup_storage(
+    options: &Options,
+    reflection: Option<&Reflection>,
+) -> Result<(), RunnerError> {
+    let used = reflection.map_or(0, |reflection| reflection.workgroup_storage);
+    let limit = max_workgroup_storage(options);
+    if used > limit {
+        return Err(RunnerError::WorkgroupStorageTooLarge { used, limit });
+    }
+    Ok(())
+}
+
+/// Creates a resource with `create`, failing with wgpu's validation errors if it raises any,
+/// attributed to the `stage` of the setup that created it.
+fn validated<T>(
+    device: &wgpu::Device,
+    stage: &'static str,
+    create: impl FnOnce() -> T,
+) -> Result<T, RunnerError> {
+    crate::capture_errors(device, create)
+        .map_err(|message| RunnerError::Validation { stage, message })
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn create_output_file(path: &Path, len: usize) -> Result<OutputFile, RunnerError> {
+    OutputFile::create(path, len)
+}
+
+/// The web has no files to map, so `--output-mmap` always fails there.
+#[cfg(target_arch = "wasm32")]
+fn create_output_file(_path: &Path, _len: usize) -> Result<OutputFile, RunnerError> {
+    Err(RunnerError::OutputFile(
+        "--output-mmap isn't supported on the web".to_string(),
+    ))
+}
+
+/// Never constructed, see the web's [`create_output_file`].
+#[cfg(target_arch = "wasm32")]
+enum OutputFile {}
+
+#[cfg(target_arch = "wasm32")]
+impl OutputFile {
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        match *self {}
+    }
+
+    fn copy_from(&mut self, _data: &[u8]) {
+        match *self {}
+    }
+
+    fn finish(self) -> Result<(), RunnerError> {
+        match self {}
+    }
+}
+
+/// An extra storage buffer bound for an `--input` or `--output`, reset from `input` before every
+/// dispatch like the main storage buffer.
+struct ExtraBuffer {
+    binding: u32,
+    /// How the shader declares the buffer, and so how it's bound.
+    ty: wgpu::BufferBindingType,
+    /// The size of the contents, without the padding to `COPY_BUFFER_ALIGNMENT`.
+    len: usize,
+    /// The size of the buffers, padded to `COPY_BUFFER_ALIGNMENT`.
+    size: wgpu::BufferAddress,
+    input: wgpu::Buffer,
+    storage: wgpu::Buffer,
+}
+
+/// How [`ComputeContext::new`] picks an adapter.
+///
+/// The `--adapter` and `--power-pref` options take precedence over the `WGPU_ADAPTER_NAME` and
+/// `WGPU_POWER_PREF` environment variables (named after wgpu's own conventions), which take
+/// precedence over wgpu's default choice, so CI can pick a GPU without changing command lines.
+/// Adapters can't be listed on the web, so names are only matched on native platforms.
+enum AdapterChoice {
+    /// The first adapter running on the CPU, for `--software`.
+    #[cfg(not(target_arch = "wasm32"))]
+    Software,
+    #[cfg(not(target_arch = "wasm32"))]
+    Named(String),
+    Preferred(wgpu::PowerPreference),
+}
+
+impl AdapterChoice {
+    /// The choice `options` make, and what made it.
+    fn new(options: &Options) -> (Self, &'static str) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if options.software {
+            return (AdapterChoice::Software, "--software");
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(name) = &options.adapter {
+            return (AdapterChoice::Named(name.clone()), "--adapter");
+        }
+        if let Some(power_preference) = options.power_pref {
+            return (AdapterChoice::Preferred(power_preference), "--power-pref");
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Ok(name) = std::env::var("WGPU_ADAPTER_NAME") {
+            return (AdapterChoice::Named(name), "WGPU_ADAPTER_NAME");
+        }
+        if let Ok(power_preference) = std::env::var("WGPU_POWER_PREF") {
+            match crate::parse_power_preference(&power_preference) {
+                Ok(power_preference) => {
+                    return (
+                        AdapterChoice::Preferred(power_preference),
+                        "WGPU_POWER_PREF",
+                    )
+                }
+                Err(err) => eprintln!("Ignoring WGPU_POWER_PREF: {}", err),
+            }
+        }
+        (
+            AdapterChoice::Preferred(wgpu::PowerPreference::default()),
+            "default",
+        )
+    }
+}
+
+/// The textures bound for a shader that samples them, and where they're bound.
+struct BoundTextures {
+    textures: Vec<SampledTexture>,
+    /// Whether the shader binds an array of textures, rather than a single one.
+    array: bool,
+    sampler_binding: u32,
+    texture_binding: u32,
+}
+
+/// The adapter, device and queue used to run compute shaders.
+///
+/// Creating these is comparatively expensive, so applications dispatching repeatedly should
+/// create a context once and reuse it across [`ComputeContext::dispatch`] calls.
+///
+/// The device and queue are shared, so applications can record and [`ComputeContext::submit`]
+/// their own work alongside the runner's, or hold on to them past the context.
+///
+/// Dropping the context waits for the device to finish all work submitted to it, so nothing is
+/// still in flight when its resources are freed.
+pub struct ComputeContext {
+    // Fields are dropped in declaration order, once `Drop::drop` has waited for the device: the
+    // queue before the device it belongs to, and both before the adapter.
+    /// The only queue of `device`. wgpu creates exactly one queue per device on every backend,
+    /// even where the underlying API has dedicated compute queues (Vulkan, D3D12, Metal), so
+    /// there's no separate async compute queue to select. Overlapping compute with graphics work
+    /// is left to the driver's scheduling of that single queue.
+    queue: Arc<wgpu::Queue>,
+    device: Arc<wgpu::Device>,
+    adapter: wgpu::Adapter,
+    /// Only kept for [`ComputeContext::memory_report`], which reports on the whole instance.
+    #[cfg(all(feature = "memory-report", not(target_arch = "wasm32")))]
+    instance: wgpu::Instance,
+    timestamp_period: f32,
+    timestamps: TimestampGranularity,
+    readback: Readback,
+    selected_by: &'static str,
+    /// Set when a dispatch timed out, in which case the device may never finish its work and
+    /// isn't waited for on drop.
+    hung: AtomicBool,
+    /// The number of submissions made to `queue` so far, the next [`Submission::index`].
+    submitted: AtomicU64,
+    /// The `--trace` of everything done with the context, written out on drop.
+    trace: Trace,
+}
+
+/// Tracks the completion of a single [`ComputeContext::submit`].
+///
+/// This version of wgpu has no `Queue::on_submitted_work_done`, so completion is tracked by
+/// mapping a small fence buffer that's written as part of the submission: mapping only resolves
+/// once the GPU is done with every submission using the buffer.
+pub struct Submission {
+    index: u64,
+    fence: wgpu::Buffer,
+    mapped: BoxFuture<'static, Result<(), wgpu::BufferAsyncError>>,
+    /// The outcome of `mapped`, once [`poll_once`] has seen it resolve.
+    finished: Option<Result<(), wgpu::BufferAsyncError>>,
+}
+
+impl Submission {
+    /// The position of the submission on its context's queue, counting from 0, which orders it
+    /// among the context's other submissions, e.g. those of [`ComputeOutput::submission`].
+    ///
+    /// `Queue::submit` returns nothing in this version of wgpu, so this stands in for the
+    /// `SubmissionIndex` later versions return, and the fence for `WaitForSubmissionIndex`.
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    /// Resolves once the GPU has finished the submission. Like every wgpu future, this only makes
+    /// progress while the device is polled.
+    pub async fn done(self) -> Result<(), RunnerError> {
+        let Self {
+            fence,
+            mapped,
+            finished,
+            ..
+        } = self;
+        let result = match finished {
+            Some(result) => result,
+            None => mapped.await,
+        };
+        drop(fence);
+        result.map_err(|_| RunnerError::MapFailed)
+    }
+}
+
+/// Polls `device` once without blocking, returning whether `submission` has finished.
+///
+/// This is for external event loops that drive the GPU on their own schedule and can't block in
+/// `Maintain::Wait`. `Device::poll` doesn't report whether the queue is idle in this version of
+/// wgpu, so completion is that of the submission's fence. A failed fence mapping also counts as
+/// finished, with [`Submission::done`] reporting the failure.
+pub fn poll_once(device: &wgpu::Device, submission: &mut Submission) -> bool {
+    device.poll(wgpu::Maintain::Poll);
+    if submission.finished.is_none() {
+        submission.finished = submission.mapped.as_mut().now_or_never();
+    }
+    submission.finished.is_some()
+}
+
+impl Drop for ComputeContext {
+    fn drop(&mut self) {
+        // Resources dropped on error paths are only freed once the GPU is done with them, which
+        // it never is if nothing polls the device again, so finish up here instead of leaving
+        // work in flight for the validation layers to complain about.
+        if !self.hung.load(Ordering::Relaxed) {
+            self.device.poll(wgpu::Maintain::Wait);
+        }
+        self.trace.write();
+    }
+}
+
+impl ComputeContext {
+    /// Creates a context on the adapter picked by `options`, or by the `WGPU_ADAPTER_NAME` and
+    /// `WGPU_POWER_PREF` environment variables if they don't pick one.
+    pub async fn new(options: &Options) -> Result<Self, RunnerError> {
+        Self::with_trace(options, Trace::new(options)).await
+    }
+
+    /// Like [`ComputeContext::new`], recording the setup and every dispatch to `trace`.
+    pub(super) async fn with_trace(options: &Options, trace: Trace) -> Result<Self, RunnerError> {
+        let setup = trace.start();
+        let backends = crate::backends(options);
+        let instance = wgpu::Instance::new(backends);
+        let (choice, selected_by) = AdapterChoice::new(options);
+        let adapter = match choice {
+            // This version of wgpu has no `force_fallback_adapter`, but software adapters
+            // (lavapipe, WARP, SwiftShader) all report themselves as running on the CPU.
+            #[cfg(not(target_arch = "wasm32"))]
+            AdapterChoice::Software => {
+                let adapter = instance
+                    .enumerate_adapters(backends)
+                    .find(|adapter| adapter.get_info().device_type == wgpu::DeviceType::Cpu)
+                    .ok_or(RunnerError::NoSoftwareAdapter)?;
+                if options.verbosity() > Verbosity::Quiet {
+                    let info = adapter.get_info();
+                    eprintln!(
+                        "Running on the software adapter {} ({:?}), which is much slower than \
+                         a GPU",
+                        info.name, info.backend
+                    );
+                }
+                adapter
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            AdapterChoice::Named(name) => {
+                let name = name.to_lowercase();
+                instance
+                    .enumerate_adapters(backends)
+                    .find(|adapter| adapter.get_info().name.to_lowercase().contains(&name))
+                    .ok_or(RunnerError::NoAdapterNamed(name))?
+            }
+            AdapterChoice::Preferred(power_preference) => instance
+                .request_adapter(&wgpu::RequestAdapterOptions {
+                    power_preference,
+                    compatible_surface: None,
+                })
+                .await
+                .ok_or(RunnerError::NoAdapter)?,
+        };
+
+        let timestamp_period = adapter.get_timestamp_period();
+        let timestamps = TimestampGranularity::new(adapter.get_info().backend);
+        let readback = Readback::new(adapter.features());
+        let mut features = wgpu::Features::TIMESTAMP_QUERY;
+        if readback == Readback::Mapped {
+            features |= wgpu::Features::MAPPABLE_PRIMARY_BUFFERS;
+        }
+        // Needed for shaders sampling arrays of textures, but only if the adapter has them. wgpu
+        // can't bind arrays of buffers yet.
+        features |= adapter.features()
+            & (wgpu::Features::SAMPLED_TEXTURE_BINDING_ARRAY
+                | wgpu::Features::UNSIZED_BINDING_ARRAY);
+        // Likewise for the features shader capabilities need, see `capability_feature`.
+        features |= adapter.features()
+            & (wgpu::Features::SHADER_FLOAT64
+                | wgpu::Features::SAMPLED_TEXTURE_ARRAY_DYNAMIC_INDEXING
+                | wgpu::Features::SAMPLED_TEXTURE_ARRAY_NON_UNIFORM_INDEXING);
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: None,
+                    features,
+                    limits: wgpu::Limits::default(),
+                },
+                None,
+            )
+            .await
+            .map_err(RunnerError::RequestDevice)?;
+        trace.end("Setup", setup);
+
+        Ok(Self {
+            queue: Arc::new(queue),
+            device: Arc::new(device),
+            adapter,
+            #[cfg(all(feature = "memory-report", not(target_arch = "wasm32")))]
+            instance,
+            timestamp_period,
+            timestamps,
+            readback,
+            selected_by,
+            hung: AtomicBool::new(false),
+            submitted: AtomicU64::new(0),
+            trace,
+        })
+    }
+
+    pub fn adapter(&self) -> &wgpu::Adapter {
+        &self.adapter
+    }
+
+    pub fn device(&self) -> &Arc<wgpu::Device> {
+        &self.device
+    }
+
+    /// The only queue of the device, see [`ComputeContext`] for why there's just the one.
+    pub fn queue(&self) -> &Arc<wgpu::Queue> {
+        &self.queue
+    }
+
+    /// wgpu's report of the resources alive in the context's instance, by backend and kind, e.g.
+    /// to catch buffers leaking across dispatches on a long-lived context.
+    ///
+    /// wgpu-core only counts the occupied and vacant slots of each kind of resource, and the size
+    /// of those slots, not the GPU memory behind them, which this version of wgpu doesn't track.
+    #[cfg(all(feature = "memory-report", not(target_arch = "wasm32")))]
+    pub fn memory_report(&self) -> String {
+        format!("{:#?}", self.instance.generate_report())
+    }
+
+    /// Submits `command_buffers`, returning a handle to await their completion individually,
+    /// rather than waiting for all of the queue's work at once.
+    pub fn submit(
+        &self,
+        command_buffers: impl IntoIterator<Item = wgpu::CommandBuffer>,
+    ) -> Submission {
+        let fence = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Submission fence"),
+            size: wgpu::COPY_BUFFER_ALIGNMENT,
+            usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+        // Queued writes are executed as part of the next submission.
+        self.queue
+            .write_buffer(&fence, 0, &[0; wgpu::COPY_BUFFER_ALIGNMENT as usize]);
+        let index = self.queue_submit(command_buffers);
+        let mapped = fence.slice(..).map_async(wgpu::MapMode::Read).boxed();
+        Submission {
+            index,
+            fence,
+            mapped,
+            finished: None,
+        }
+    }
+
+    /// Waits for exactly `submission`, without also waiting for whatever was submitted after it,
+    /// as blocking in `Maintain::Wait` would. Fails with [`RunnerError::Timeout`] if it takes
+    /// longer than `timeout`, except on the web, where it's ignored as by [`wait_for`].
+    pub async fn wait(
+        &self,
+        mut submission: Submission,
+        timeout: Option<Duration>,
+    ) -> Result<(), RunnerError> {
+        if cfg!(target_arch = "wasm32") {
+            return submission.done().await;
+        }
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        while !poll_once(&self.device, &mut submission) {
+            if let (Some(timeout), Some(deadline)) = (timeout, deadline) {
+                if Instant::now() >= deadline {
+                    self.hung.store(true, Ordering::Relaxed);
+                    return Err(RunnerError::Timeout(timeout));
+                }
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        submission.done().await
+    }
+
+    /// Submits `command_buffers` to the queue, returning the index of the submission.
+    fn queue_submit(&self, command_buffers: impl IntoIterator<Item = wgpu::CommandBuffer>) -> u64 {
+        self.queue.submit(command_buffers);
+        self.submitted.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Reads the first `len` bytes of `buffer` back through a buffer of `chunk_size` bytes, one
+    /// chunk at a time, for buffers larger than can be mapped at once.
+    async fn read_back_chunked(
+        &self,
+        buffer: &wgpu::Buffer,
+        len: usize,
+        chunk_size: wgpu::BufferAddress,
+        options: &Options,
+    ) -> Result<Vec<u8>, RunnerError> {
+        let mut contents = vec![0; len];
+        self.read_back_chunked_into(buffer, &mut contents, chunk_size, options)
+            .await?;
+        Ok(contents)
+    }
+
+    /// Like [`ComputeContext::read_back_chunked`], reading the first `dest.len()` bytes of
+    /// `buffer` into `dest`.
+    async fn read_back_chunked_into(
+        &self,
+        buffer: &wgpu::Buffer,
+        dest: &mut [u8],
+        chunk_size: wgpu::BufferAddress,
+        options: &Options,
+    ) -> Result<(), RunnerError> {
+        let len = dest.len();
+        let chunk_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Readback chunk"),
+            size: chunk_size,
+            usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let size = aligned_buffer_size(len);
+        let mut offset = 0;
+        while offset < size {
+            let copied = chunk_size.min(size - offset);
+            let mut encoder = self
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+            encoder.copy_buffer_to_buffer(buffer, offset, &chunk_buffer, 0, copied);
+            self.queue_submit(Some(encoder.finish()));
+            let slice = chunk_buffer.slice(..copied);
+            wait_for(
+                &self.device,
+                slice.map_async(wgpu::MapMode::Read),
+                options.timeout.map(Duration::from_secs),
+            )
+            .await
+            .map_err(|err| {
+                self.hung.store(true, Ordering::Relaxed);
+                err
+            })?
+            .map_err(|_| RunnerError::MapFailed)?;
+            // Only the last chunk has padding past the logical size to leave out.
+            let start = offset as usize;
+            let wanted = (len - start).min(copied as usize);
+            dest[start..start + wanted].copy_from_slice(&slice.get_mapped_range()[..wanted]);
+            chunk_buffer.unmap();
+            offset += copied;
+        }
+        Ok(())
+    }
+
+    /// Runs the `main_cs` entry point of `shader_binary` over `src`, returning the contents of
+    /// the storage buffer afterwards.
+    ///
+    /// The shader is dispatched once per `options.iterations`, after a number of untimed warmup
+    /// dispatches, each starting from a fresh copy of `src`.
+    pub async fn dispatch(
+        &self,
+        options: &Options,
+        shader_binary: &wgpu::ShaderModuleDescriptor<'_>,
+        src: &[u32],
+    ) -> Result<ComputeOutput, RunnerError> {
+        let passthrough = crate::passthrough(options, self.adapter.get_info().backend);
+        self.dispatch_module(options, shader_binary, src, passthrough)
+            .await
+            .map_err(|err| with_source_locations(err, shader_binary, &options.entry_point))
+    }
+
+    /// Like [`ComputeContext::dispatch`], passing the module through to the driver without naga
+    /// validating it if `passthrough`, regardless of `--passthrough`.
+    pub(super) async fn dispatch_module(
+        &self,
+        options: &Options,
+        shader_binary: &wgpu::ShaderModuleDescriptor<'_>,
+        src: &[u32],
+        passthrough: bool,
+    ) -> Result<ComputeOutput, RunnerError> {
+        let device = &self.device;
+        let trace = &self.trace;
+        let warmup = warmup_count(options);
+        let iterations = options.iterations.max(1);
+        let pipeline_start = trace.start();
+
+        if let wgpu::ShaderSource::SpirV(spirv) = &shader_binary.source {
+            check_capabilities(device.features(), spirv)?;
+        }
+        let unvalidated;
+        let module_desc = if passthrough {
+            unvalidated = crate::without_validation(shader_binary);
+            &unvalidated
+        } else {
+            shader_binary
+        };
+        // Load the shaders from disk
+        let module =
+            crate::create_shader_module(device, module_desc).map_err(RunnerError::ShaderModule)?;
+        let reflection = reflect(shader_binary)?;
+        check_element_stride(reflection.as_ref(), 0, ELEMENT_STRIDE)?;
+        check_workgroup_storage(options, reflection.as_ref())?;
+        let local_size = local_size(reflection.as_ref(), &options.entry_point);
+        let workgroups = workgroup_counts(options, src.len(), local_size)?;
+        let invocations = workgroups
+            .iter()
+            .chain(&local_size)
+            .map(|&count| u64::from(count))
+            .product();
+
+        let padded_len = padded_len(options, src.len(), workgroups, local_size);
+        let mut src_bytes = src
+            .iter()
+            .copied()
+            .chain(std::iter::repeat(PADDING_SENTINEL))
+            .take(padded_len)
+            // Not sure which endianness is correct to use here
+            .map(u32::to_ne_bytes)
+            .flat_map(core::array::IntoIter::new)
+            .collect::<Vec<_>>();
+        // The buffers hold the padding for the whole last workgroup, and copies must be a multiple
+        // of `COPY_BUFFER_ALIGNMENT` in size on top of that, but only the first `logical_size`
+        // bytes, those of the actual inputs, are read back.
+        let logical_size = src.len() * 4;
+        let buffer_size = aligned_buffer_size(padded_len * 4);
+        src_bytes.resize(buffer_size as usize, 0);
+        // Created before any GPU work, so a file that can't be written fails the run right away.
+        let mut output_file = match &options.output_mmap {
+            Some(path) if !options.count_only => Some(create_output_file(path, logical_size)?),
+            _ => None,
+        };
+
+        // Shaders that sample a texture get the one given by `--texture`, paired with a sampler,
+        // and shaders sampling an array of textures get all of them.
+        let bound_textures = match reflection.as_ref().map(|reflection| {
+            (
+                reflection.binding_of_kind(0, BindingKind::Sampler),
+                reflection.binding_of_kind(0, BindingKind::Texture),
+            )
+        }) {
+            Some((Some(sampler), Some(texture))) => {
+                let expected = match texture.array {
+                    None => Some(1),
+                    Some(ArrayLength::Fixed(len)) => Some(len as usize),
+                    Some(ArrayLength::Runtime) => None,
+                };
+                let given = options.texture.len();
+                if given == 0 {
+                    return Err(RunnerError::MissingTexture);
+                }
+                if let Some(expected) = expected.filter(|&expected| expected != given) {
+                    return Err(RunnerError::TextureCount { expected, given });
+                }
+                if let Some(array) = texture.array {
+                    let mut required = wgpu::Features::SAMPLED_TEXTURE_BINDING_ARRAY;
+                    if array == ArrayLength::Runtime {
+                        required |= wgpu::Features::UNSIZED_BINDING_ARRAY;
+                    }
+                    if !device.features().contains(required) {
+                        return Err(RunnerError::MissingFeatures(required));
+                    }
+                }
+                let filter = match options.filter {
+                    Filter::Nearest => wgpu::FilterMode::Nearest,
+                    Filter::Linear => wgpu::FilterMode::Linear,
+                };
+                let textures = options
+                    .texture
+                    .iter()
+                    .map(|path| SampledTexture::from_png(device, &self.queue, path, filter))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|err| RunnerError::TextureLoadFailed(err.to_string()))?;
+                Some(BoundTextures {
+                    textures,
+                    array: texture.array.is_some(),
+                    sampler_binding: sampler.binding,
+                    texture_binding: texture.binding,
+                })
+            }
+            _ => None,
+        };
+        let texture_views = bound_textures
+            .iter()
+            .flat_map(|bound| bound.textures.iter().map(SampledTexture::view))
+            .collect::<Vec<_>>();
+
+        let extra_buffers = extra_buffer_contents(options, reflection.as_ref(), invocations)?
+            .into_iter()
+            .map(|(binding, mut contents)| {
+                let len = contents.len();
+                contents.resize(
+                    aligned_buffer_size(len).max(wgpu::COPY_BUFFER_ALIGNMENT) as usize,
+                    0,
+                );
+                let input = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Buffer file input"),
+                    contents: &contents,
+                    usage: wgpu::BufferUsage::COPY_SRC,
+                });
+                let ty = buffer_binding_type(reflection.as_ref(), 0, binding);
+                let storage = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Buffer file storage"),
+                    size: contents.len() as wgpu::BufferAddress,
+                    usage: binding_usage(ty)
+                        | wgpu::BufferUsage::COPY_DST
+                        | wgpu::BufferUsage::COPY_SRC,
+                    mapped_at_creation: false,
+                });
+                ExtraBuffer {
+                    binding,
+                    ty,
+                    len,
+                    size: contents.len() as wgpu::BufferAddress,
+                    input,
+                    storage,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let storage_ty = buffer_binding_type(reflection.as_ref(), 0, 0);
+        let mut layout_entries = vec![
+            // XXX - some graphics cards do not support empty bind layout groups, so
+            // create a dummy entry.
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                count: None,
+                visibility: wgpu::ShaderStage::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    has_dynamic_offset: false,
+                    min_binding_size: min_binding_size(reflection.as_ref(), 0, 0),
+                    ty: storage_ty,
+                },
+            },
+        ];
+        layout_entries.extend(
+            extra_buffers
+                .iter()
+                .map(|extra| wgpu::BindGroupLayoutEntry {
+                    binding: extra.binding,
+                    count: None,
+                    visibility: wgpu::ShaderStage::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        has_dynamic_offset: false,
+                        min_binding_size: min_binding_size(reflection.as_ref(), 0, extra.binding),
+                        ty: extra.ty,
+                    },
+                }),
+        );
+        if let Some(bound) = &bound_textures {
+            let count = if bound.array {
+                NonZeroU32::new(bound.textures.len() as u32)
+            } else {
+                None
+            };
+            layout_entries.extend(core::array::IntoIter::new(SampledTexture::layout_entries(
+                bound.sampler_binding,
+                bound.texture_binding,
+                count,
+            )));
+        }
+        let bind_group_layout = validated(device, "bind group layout", || {
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: None,
+                entries: &layout_entries,
+            })
+        })?;
+
+        let pipeline_layout = validated(device, "pipeline layout", || {
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: None,
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            })
+        })?;
+
+        let compute_pipeline = validated(device, "compute pipeline", || {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: None,
+                layout: Some(&pipeline_layout),
+                module: &module,
+                entry_point: &options.entry_point,
+            })
+        })
+        .map(Arc::new)?;
+        trace.end("Create pipeline", pipeline_start);
+        let upload = trace.start();
+
+        // Storage buffers larger than `--max-map-size` are read back a chunk at a time instead,
+        // through a buffer of their own. With `--count-only`, it isn't read back at all.
+        let chunk_size = readback_chunk_size(options, buffer_size).filter(|_| !options.count_only);
+        let copied_back = self.readback != Readback::Mapped && !options.count_only;
+        let readback_buffer = if !copied_back || chunk_size.is_some() {
+            None
+        } else {
+            Some(validated(device, "readback buffer", || {
+                device.create_buffer(&wgpu::BufferDescriptor {
+                    label: None,
+                    size: buffer_size,
+                    // Can be read to the CPU, and can be copied from the shader's storage buffer
+                    usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+                    mapped_at_creation: false,
+                })
+            })?)
+        };
+
+        // The shader works in place, so every dispatch starts by copying the input from here.
+        let input_buffer = validated(device, "input buffer", || {
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Collatz Conjecture Input"),
+                contents: &src_bytes,
+                usage: wgpu::BufferUsage::COPY_SRC,
+            })
+        })?;
+
+        // wgpu has no `clear_buffer` yet, so `--clear` copies from a buffer of the fill value.
+        let clear_range = options.clear_range.clone().unwrap_or(0..src.len() as u32);
+        if options.clear.is_some()
+            && (clear_range.start > clear_range.end || clear_range.end as usize > src.len())
+        {
+            return Err(RunnerError::ClearOutOfBounds {
+                range: clear_range,
+                len: src.len(),
+            });
+        }
+        let clear = match options.clear {
+            Some(value) if !clear_range.is_empty() => {
+                let range = clear_range;
+                let fill = std::iter::repeat(value)
+                    .take(range.len())
+                    .map(u32::to_ne_bytes)
+                    .flat_map(core::array::IntoIter::new)
+                    .collect::<Vec<_>>();
+                let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Clear value"),
+                    contents: &fill,
+                    usage: wgpu::BufferUsage::COPY_SRC,
+                });
+                Some((buffer, range))
+            }
+            _ => None,
+        };
+
+        let mut storage_usage =
+            binding_usage(storage_ty) | wgpu::BufferUsage::COPY_DST | wgpu::BufferUsage::COPY_SRC;
+        if self.readback == Readback::Mapped {
+            storage_usage |= wgpu::BufferUsage::MAP_READ;
+        }
+        let storage_buffer = validated(device, "storage buffer", || {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Storage buffer"),
+                size: buffer_size,
+                usage: storage_usage,
+                mapped_at_creation: false,
+            })
+        })?;
+
+        // `resolve_query_set` only requires its destination to be `COPY_DST` in this version of
+        // wgpu, on every backend. Later versions add a dedicated `QUERY_RESOLVE` usage, which will
+        // need adding here when upgrading.
+        let timestamp_buffer = validated(device, "timestamps buffer", || {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Timestamps buffer"),
+                size: 16 * u64::from(iterations),
+                usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+                mapped_at_creation: false,
+            })
+        })?;
+
+        check_binding_size(reflection.as_ref(), 0, 0, buffer_size)?;
+        for extra in &extra_buffers {
+            check_binding_size(reflection.as_ref(), 0, extra.binding, extra.size)?;
+        }
+        let mut bind_group_entries = vec![wgpu::BindGroupEntry {
+            binding: 0,
+            resource: storage_buffer.as_entire_binding(),
+        }];
+        bind_group_entries.extend(extra_buffers.iter().map(|extra| wgpu::BindGroupEntry {
+            binding: extra.binding,
+            resource: extra.storage.as_entire_binding(),
+        }));
+        if let Some(bound) = &bound_textures {
+            // Every texture has its own sampler, but they're all configured the same.
+            bind_group_entries.extend(core::array::IntoIter::new(
+                bound.textures[0].bind_group_entries(
+                    bound.sampler_binding,
+                    bound.texture_binding,
+                    if bound.array {
+                        Some(&texture_views[..])
+                    } else {
+                        None
+                    },
+                ),
+            ));
+        }
+        let bind_group = validated(device, "bind group", || {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: None,
+                layout: &bind_group_layout,
+                entries: &bind_group_entries,
+            })
+        })?;
+
+        let queries = validated(device, "query set", || {
+            device.create_query_set(&wgpu::QuerySetDescriptor {
+                count: 2 * iterations,
+                ty: wgpu::QueryType::Timestamp,
+            })
+        })?;
+
+        trace.end("Upload", upload);
+
+        // Each dispatch is its own submission, so the GPU can start on the first ones while the
+        // rest are still being encoded.
+        let encode = trace.start();
+        let mut submissions = Vec::with_capacity((warmup + iterations + 1) as usize);
+        for i in 0..warmup + iterations {
+            let mut encoder =
+                device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+            encoder.copy_buffer_to_buffer(&input_buffer, 0, &storage_buffer, 0, buffer_size);
+            for extra in &extra_buffers {
+                encoder.copy_buffer_to_buffer(&extra.input, 0, &extra.storage, 0, extra.size);
+            }
+            if let Some((clear_buffer, range)) = &clear {
+                encoder.copy_buffer_to_buffer(
+                    clear_buffer,
+                    0,
+                    &storage_buffer,
+                    4 * u64::from(range.start),
+                    4 * range.len() as wgpu::BufferAddress,
+                );
+            }
+            let timed = i.checked_sub(warmup);
+            let in_pass = self.timestamps == TimestampGranularity::Pass;
+            if let (Some(timed), false) = (timed, in_pass) {
+                encoder.write_timestamp(&queries, 2 * timed);
+            }
+            let mut cpass =
+                encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
+            cpass.set_bind_group(0, &bind_group, &[]);
+            cpass.set_pipeline(&compute_pipeline);
+            if let (Some(timed), true) = (timed, in_pass) {
+                cpass.write_timestamp(&queries, 2 * timed);
+            }
+            cpass.dispatch(workgroups[0], workgroups[1], workgroups[2]);
+            if let (Some(timed), true) = (timed, in_pass) {
+                cpass.write_timestamp(&queries, 2 * timed + 1);
+            }
+            drop(cpass);
+            if let (Some(timed), false) = (timed, in_pass) {
+                encoder.write_timestamp(&queries, 2 * timed + 1);
+            }
+            submissions.push(self.submit(Some(encoder.finish())));
+        }
+
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        if let Some(readback_buffer) = &readback_buffer {
+            encoder.copy_buffer_to_buffer(&storage_buffer, 0, readback_buffer, 0, buffer_size);
+        }
+        encoder.resolve_query_set(&queries, 0..2 * iterations, &timestamp_buffer, 0);
+        let output_readbacks = extra_buffers
+            .iter()
+            .filter(|extra| {
+                options
+                    .output
+                    .iter()
+                    .any(|file| file.binding == extra.binding)
+                    || options
+                        .append
+                        .iter()
+                        .any(|append| append.binding == extra.binding)
+                    || extra.binding == DEBUG_BINDING
+                    || (options.count_only && extra.binding == COUNT_BINDING)
+            })
+            .map(|extra| {
+                let readback = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Buffer file readback"),
+                    size: extra.size,
+                    usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                encoder.copy_buffer_to_buffer(&extra.storage, 0, &readback, 0, extra.size);
+                (extra, readback)
+            })
+            .collect::<Vec<_>>();
+
+        let readback_submission = self.submit(Some(encoder.finish()));
+        let submission = readback_submission.index();
+        submissions.push(readback_submission);
+        trace.end("Encode and submit", encode);
+        let wait = trace.start();
+        let mapped_buffer = readback_buffer.as_ref().unwrap_or(&storage_buffer);
+        let buffer_slice = mapped_buffer.slice(..);
+        let timestamp_slice = timestamp_buffer.slice(..);
+        let timestamp_future = timestamp_slice.map_async(wgpu::MapMode::Read);
+        let buffer_future = if chunk_size.is_none() && !options.count_only {
+            buffer_slice.map_async(wgpu::MapMode::Read).left_future()
+        } else {
+            ready(Ok(())).right_future()
+        };
+        let outputs_future = join_all(
+            output_readbacks
+                .iter()
+                .map(|(_, readback)| readback.slice(..).map_async(wgpu::MapMode::Read)),
+        );
+        let submitted = try_join_all(submissions.into_iter().map(Submission::done));
+        let ((mapped, outputs_mapped), submitted) = wait_for(
+            device,
+            join(
+                join(join(buffer_future, timestamp_future), outputs_future),
+                submitted,
+            ),
+            options.timeout.map(Duration::from_secs),
+        )
+        .await
+        .map_err(|err| {
+            self.hung.store(true, Ordering::Relaxed);
+            err
+        })?;
+        submitted?;
+        trace.end("Wait", wait);
+        let read_start = trace.start();
+
+        if let ((Ok(()), Ok(())), true) = (mapped, outputs_mapped.iter().all(Result::is_ok)) {
+            let mut outputs = Vec::with_capacity(output_readbacks.len());
+            let mut appended = Vec::new();
+            let mut debug = None;
+            let mut count = None;
+            for (extra, readback) in &output_readbacks {
+                let mut contents = readback.slice(..).get_mapped_range()[..extra.len].to_vec();
+                readback.unmap();
+                if options.count_only && extra.binding == COUNT_BINDING {
+                    count = Some(u32::from_ne_bytes(contents[..4].try_into().unwrap()));
+                    if !options
+                        .output
+                        .iter()
+                        .any(|file| file.binding == COUNT_BINDING)
+                    {
+                        continue;
+                    }
+                }
+                if extra.binding == DEBUG_BINDING {
+                    // `debug_binding` has already checked the binding when creating the buffer.
+                    let record_words = debug_binding(reflection.as_ref())
+                        .ok()
+                        .flatten()
+                        .map_or(1, debug_record_words);
+                    debug = Some(DebugRecords {
+                        record_words,
+                        words: result_words(&contents),
+                    });
+                    if !options
+                        .output
+                        .iter()
+                        .any(|file| file.binding == DEBUG_BINDING)
+                    {
+                        continue;
+                    }
+                }
+                let append = options
+                    .append
+                    .iter()
+                    .find(|append| append.binding == extra.binding);
+                let binding = reflection
+                    .as_ref()
+                    .and_then(|reflection| reflection.binding(0, extra.binding));
+                if let (Some(append), Some(binding)) = (append, binding) {
+                    // Only the elements the cursor counts were written, and only as many as fit.
+                    let (header, element_size) = append_layout(binding);
+                    let count = u32::from_ne_bytes(contents[..4].try_into().unwrap());
+                    let stored = count.min(append.capacity) as usize;
+                    contents = contents[header..header + stored * element_size].to_vec();
+                    appended.push(Appended {
+                        binding: extra.binding,
+                        count,
+                        capacity: append.capacity,
+                    });
+                }
+                outputs.push((extra.binding, contents));
+            }
+            let result = match (output_file.take(), chunk_size) {
+                _ if options.count_only => Vec::new(),
+                (None, None) => {
+                    let data = buffer_slice.get_mapped_range();
+                    let result = logical_result(&data, logical_size);
+                    drop(data);
+                    mapped_buffer.unmap();
+                    result
+                }
+                (None, Some(chunk_size)) => result_words(
+                    &self
+                        .read_back_chunked(&storage_buffer, logical_size, chunk_size, options)
+                        .await?,
+                ),
+                // The results are only in the file, never all in memory at once.
+                (Some(mut file), None) => {
+                    let data = buffer_slice.get_mapped_range();
+                    file.copy_from(&data[..logical_size]);
+                    drop(data);
+                    mapped_buffer.unmap();
+                    file.finish()?;
+                    Vec::new()
+                }
+                (Some(mut file), Some(chunk_size)) => {
+                    self.read_back_chunked_into(
+                        &storage_buffer,
+                        file.bytes_mut(),
+                        chunk_size,
+                        options,
+                    )
+                    .await?;
+                    file.finish()?;
+                    Vec::new()
+                }
+            };
+            let timing_data = timestamp_slice.get_mapped_range();
+            let timings = timing_data
+                .chunks_exact(8)
+                .map(|b| u64::from_ne_bytes(b.try_into().unwrap()))
+                .collect::<Vec<_>>();
+            drop(timing_data);
+            timestamp_buffer.unmap();
+            let gpu_times = timings
+                .chunks_exact(2)
+                .map(|pair| {
+                    Duration::from_nanos(
+                        ((pair[1] - pair[0]) as f64 * f64::from(self.timestamp_period)) as u64,
+                    )
+                })
+                .collect::<Vec<_>>();
+            trace.end("Readback", read_start);
+            // The timed dispatches are the last GPU work before the wait ended, so they're laid
+            // out back to back up to its end.
+            let mut gpu_start =
+                read_start.map(|end| end.checked_sub(gpu_times.iter().sum()).unwrap_or(end));
+            for (i, time) in gpu_times.iter().enumerate() {
+                trace.gpu(format!("Dispatch {}", i), gpu_start, *time);
+                gpu_start = gpu_start.map(|start| start + *time);
+            }
+            Ok(ComputeOutput {
+                summary: ValuesSummary::new(&result),
+                float_summary: if options.output_type == OutputType::F32 {
+                    Some(FloatSummary::new(&result))
+                } else {
+                    None
+                },
+                hash: if options.hash {
+                    Some(result_hash(&result))
+                } else {
+                    None
+                },
+                result,
+                workgroups,
+                local_size,
+                invocations,
+                timing: TimingSummary::new(&gpu_times),
+                gpu_times,
+                timestamps: self.timestamps,
+                readback: self.readback,
+                warmup,
+                adapter: AdapterSummary::new(self.adapter.get_info(), self.selected_by),
+                features: feature_names(device.features()),
+                passthrough,
+                submission,
+                outputs,
+                appended,
+                debug,
+                count,
+                pipeline: Some(compute_pipeline),
+            })
+        } else {
+            Err(RunnerError::MapFailed)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn aligned_buffer_sizes() {
+        assert_eq!(aligned_buffer_size(0), 0);
+        assert_eq!(aligned_buffer_size(1), 4);
+        assert_eq!(aligned_buffer_size(3), 4);
+        assert_eq!(aligned_buffer_size(4), 4);
+        assert_eq!(aligned_buffer_size(5), 8);
+    }
+
+    #[test]
+    fn logical_result_trims_padding() {
+        let mut data = [1u32, 2, 3]
+            .iter()
+            .flat_map(|word| word.to_ne_bytes().to_vec())
+            .collect::<Vec<_>>();
+        let logical_size = data.len();
+        data.resize(aligned_buffer_size(logical_size + 1) as usize, 0xff);
+        assert_eq!(logical_result(&data, logical_size), [1, 2, 3]);
+        assert_eq!(logical_result(&data, 0), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn workgroup_counts_round_up() {
+        let options = Options::default();
+        let counts = |len| workgroup_counts(&options, len, [64, 1, 1]).unwrap();
+        assert_eq!(counts(0), [0, 1, 1]);
+        assert_eq!(counts(1), [1, 1, 1]);
+        assert_eq!(counts(64), [1, 1, 1]);
+        assert_eq!(counts(65), [2, 1, 1]);
+        // A local size of 0 can only come from a broken reflection, and mustn't divide by it.
+        assert_eq!(workgroup_counts(&options, 3, [0, 1, 1]).unwrap(), [3, 1, 1]);
+    }
+
+    #[test]
+    fn workgroup_counts_of_dispatch_dims() {
+        let mut options = Options::default();
+        options.dispatch_dims = Some([100, 10, 1]);
+        // The input's length doesn't matter once the grid is given.
+        let counts = workgroup_counts(&options, 7, [8, 8, 1]).unwrap();
+        assert_eq!(counts, [13, 2, 1]);
+    }
+
+    #[test]
+    fn too_many_workgroups() {
+        let options = Options::default();
+        let len = (MAX_WORKGROUPS_PER_DIMENSION as usize + 1) * 64;
+        assert!(workgroup_counts(&options, len - 64, [64, 1, 1]).is_ok());
+        assert!(matches!(
+            workgroup_counts(&options, len, [64, 1, 1]),
+            Err(RunnerError::TooManyWorkgroups { workgroups, limit: MAX_WORKGROUPS_PER_DIMENSION })
+                if workgroups == MAX_WORKGROUPS_PER_DIMENSION as usize + 1
+        ));
+    }
+
+    #[test]
+    fn padded_len_fills_workgroups() {
+        let mut options = Options::default();
+        assert_eq!(padded_len(&options, 65, [2, 1, 1], [64, 1, 1]), 128);
+        assert_eq!(padded_len(&options, 64, [1, 1, 1], [64, 1, 1]), 64);
+        assert_eq!(padded_len(&options, 0, [0, 1, 1], [64, 1, 1]), 0);
+        // With --dispatch-dims the shader skips what's past the grid, so nothing is padded.
+        options.dispatch_dims = Some([100, 10, 1]);
+        assert_eq!(padded_len(&options, 65, [13, 2, 1], [8, 8, 1]), 65);
+    }
+}
@@ -1,8 +1,10 @@
 use std::thread::spawn;
 
 use crate::maybe_watch;
+use crate::reflect::{ComponentType, EntryPoint, Reflection};
 
 use super::Options;
+use rspirv::spirv::{BuiltIn, Capability, ExecutionModel, StorageClass};
 use shared::ShaderConstants;
 use winit::{
     event::{ElementState, Event, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent},
@@ -41,11 +43,14 @@ fn mouse_button_index(button: MouseButton) -> usize {
 async fn run(
     event_loop: EventLoop<wgpu::ShaderModuleDescriptor<'static>>,
     window: Window,
-    swapchain_format: wgpu::TextureFormat,
+    surface_format: Option<wgpu::TextureFormat>,
     shader_binary: wgpu::ShaderModuleDescriptor<'static>,
+    backends: wgpu::BackendBit,
+    present_mode: wgpu::PresentMode,
+    features: wgpu::Features,
 ) {
     let size = window.inner_size();
-    let instance = wgpu::Instance::new(wgpu::BackendBit::VULKAN | wgpu::BackendBit::METAL);
+    let instance = wgpu::Instance::new(backends);
 
     // Wait for Resumed event on Android; the surface is only needed early to
     // find an adapter that can render to this surface.
@@ -64,37 +69,34 @@ async fn run(
         .await
         .expect("Failed to find an appropriate adapter");
 
-    let features = wgpu::Features::PUSH_CONSTANTS;
-    let limits = wgpu::Limits {
-        max_push_constant_size: 256,
-        ..Default::default()
+    let (swapchain_format, selected_by) = match (surface_format, &surface) {
+        (Some(format), _) => (format, "--surface-format"),
+        (None, Some(surface)) => (
+            preferred_srgb(adapter.get_swap_chain_preferred_format(surface)),
+            "the surface",
+        ),
+        // Android only has a surface once resumed, by which point the pipeline is created.
+        (None, None) => (swapchain_format(), "the platform default"),
     };
+    println!(
+        "Surface format: {:?}, selected by {}",
+        swapchain_format, selected_by
+    );
 
     // Create the logical device and command queue
+    if let Err(missing) = check_features(&adapter, features) {
+        panic!("{}", missing);
+    }
     let (device, queue) = adapter
-        .request_device(
-            &wgpu::DeviceDescriptor {
-                label: None,
-                features,
-                limits,
-            },
-            None,
-        )
+        .request_device(&device_descriptor(features), None)
         .await
         .expect("Failed to create device");
 
     // Load the shaders from disk
 
-    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-        label: None,
-        bind_group_layouts: &[],
-        push_constant_ranges: &[wgpu::PushConstantRange {
-            stages: wgpu::ShaderStage::all(),
-            range: 0..std::mem::size_of::<ShaderConstants>() as u32,
-        }],
-    });
+    let pipeline_layout = create_pipeline_layout(&device);
 
-    let mut render_pipeline =
+    let (mut render_pipeline, mut vertex_buffer) =
         create_pipeline(&device, &pipeline_layout, swapchain_format, shader_binary);
 
     let mut sc_desc = wgpu::SwapChainDescriptor {
@@ -102,8 +104,14 @@ async fn run(
         format: swapchain_format,
         width: size.width,
         height: size.height,
-        present_mode: wgpu::PresentMode::Mailbox,
+        present_mode,
     };
+    // This version of wgpu can't be asked which present modes a surface supports. It falls back
+    // to `Fifo` when creating the swap chain instead, logging a warning.
+    println!(
+        "Present mode: {:?}, or Fifo if the surface doesn't support it",
+        present_mode
+    );
 
     let mut swap_chain = surface
         .as_ref()
@@ -124,6 +132,7 @@ async fn run(
         // the resources are properly cleaned up.
         let _ = (&instance, &adapter, &pipeline_layout);
         let render_pipeline = &mut render_pipeline;
+        let vertex_buffer = &mut vertex_buffer;
 
         *control_flow = ControlFlow::Wait;
         match event {
@@ -200,10 +209,13 @@ async fn run(
                         };
 
                         rpass.set_pipeline(render_pipeline);
+                        if let Some(vertex_buffer) = vertex_buffer {
+                            rpass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                        }
                         rpass.set_push_constants(wgpu::ShaderStage::all(), 0, unsafe {
                             any_as_u8_slice(&push_constants)
                         });
-                        rpass.draw(0..3, 0..1);
+                        rpass.draw(0..VERTEX_COUNT, 0..1);
                     }
 
                     queue.submit(Some(encoder.finish()));
@@ -257,8 +269,10 @@ async fn run(
                 }
             }
             Event::UserEvent(new_module) => {
-                *render_pipeline =
+                let (new_pipeline, new_vertex_buffer) =
                     create_pipeline(&device, &pipeline_layout, swapchain_format, new_module);
+                *render_pipeline = new_pipeline;
+                *vertex_buffer = new_vertex_buffer;
                 window.request_redraw();
                 *control_flow = ControlFlow::Poll;
             }
@@ -267,20 +281,230 @@ async fn run(
     });
 }
 
+/// The device the shaders need: they take their `ShaderConstants` as push constants.
+/// The features `--feature` requests, as a set.
+fn requested_features(options: &Options) -> wgpu::Features {
+    options
+        .features
+        .iter()
+        .fold(wgpu::Features::empty(), |all, &feature| all | feature)
+}
+
+/// Checks `adapter` supports every one of the `requested` features, failing with those it
+/// doesn't.
+fn check_features(
+    adapter: &wgpu::Adapter,
+    requested: wgpu::Features,
+) -> Result<(), crate::RunnerError> {
+    let missing = requested - adapter.features();
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(crate::RunnerError::MissingFeatures(missing))
+    }
+}
+
+/// The device the shaders render with: push constants for [`ShaderConstants`], and the
+/// `requested` features on top.
+fn device_descriptor(requested: wgpu::Features) -> wgpu::DeviceDescriptor<'static> {
+    wgpu::DeviceDescriptor {
+        label: None,
+        features: wgpu::Features::PUSH_CONSTANTS | requested,
+        limits: wgpu::Limits {
+            max_push_constant_size: 256,
+            ..Default::default()
+        },
+    }
+}
+
+fn create_pipeline_layout(device: &wgpu::Device) -> wgpu::PipelineLayout {
+    device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[],
+        push_constant_ranges: &[wgpu::PushConstantRange {
+            stages: wgpu::ShaderStage::all(),
+            range: 0..std::mem::size_of::<ShaderConstants>() as u32,
+        }],
+    })
+}
+
+/// The vertices drawn each frame, a single triangle covering the window.
+const VERTEX_COUNT: u32 = 3;
+
+/// The attributes `entry_point` reads, packed into a single vertex buffer in location order,
+/// along with the stride of each vertex in that buffer.
+fn vertex_attributes(
+    entry_point: &EntryPoint,
+) -> Result<(wgpu::BufferAddress, Vec<wgpu::VertexAttribute>), String> {
+    let mut inputs = entry_point
+        .interface
+        .iter()
+        .filter(|var| var.storage_class == StorageClass::Input)
+        .collect::<Vec<_>>();
+    inputs.sort_by_key(|var| var.location);
+    let mut offset = 0;
+    let mut attributes = Vec::with_capacity(inputs.len());
+    for var in inputs {
+        let format = match var.format {
+            Some((ComponentType::Float, 1)) => wgpu::VertexFormat::Float,
+            Some((ComponentType::Float, 2)) => wgpu::VertexFormat::Float2,
+            Some((ComponentType::Float, 3)) => wgpu::VertexFormat::Float3,
+            Some((ComponentType::Float, 4)) => wgpu::VertexFormat::Float4,
+            Some((ComponentType::Uint, 1)) => wgpu::VertexFormat::Uint,
+            Some((ComponentType::Uint, 2)) => wgpu::VertexFormat::Uint2,
+            Some((ComponentType::Uint, 3)) => wgpu::VertexFormat::Uint3,
+            Some((ComponentType::Uint, 4)) => wgpu::VertexFormat::Uint4,
+            Some((ComponentType::Sint, 1)) => wgpu::VertexFormat::Int,
+            Some((ComponentType::Sint, 2)) => wgpu::VertexFormat::Int2,
+            Some((ComponentType::Sint, 3)) => wgpu::VertexFormat::Int3,
+            Some((ComponentType::Sint, 4)) => wgpu::VertexFormat::Int4,
+            _ => {
+                return Err(format!(
+                    "the input at location {} isn't a 32-bit scalar or vector",
+                    var.location
+                ))
+            }
+        };
+        attributes.push(wgpu::VertexAttribute {
+            format,
+            offset,
+            shader_location: var.location,
+        });
+        offset += format.size();
+    }
+    Ok((offset, attributes))
+}
+
+/// Describes how the outputs of `vertex` don't match the inputs of `fragment`, if they don't.
+fn interface_mismatches(vertex: &EntryPoint, fragment: &EntryPoint) -> Vec<String> {
+    let mut mismatches = Vec::new();
+    for input in fragment
+        .interface
+        .iter()
+        .filter(|var| var.storage_class == StorageClass::Input)
+    {
+        let output = vertex.interface.iter().find(|var| {
+            var.storage_class == StorageClass::Output && var.location == input.location
+        });
+        match output {
+            None => mismatches.push(format!(
+                "{} reads location {}, which {} doesn't write",
+                fragment.name, input.location, vertex.name
+            )),
+            Some(output) if output.format != input.format => mismatches.push(format!(
+                "location {} is {:?} in {}, but {:?} in {}",
+                input.location, output.format, vertex.name, input.format, fragment.name
+            )),
+            Some(output) if output.interpolation != input.interpolation => {
+                mismatches.push(format!(
+                    "location {} is interpolated {:?} in {}, but {:?} in {}",
+                    input.location,
+                    output.interpolation,
+                    vertex.name,
+                    input.interpolation,
+                    fragment.name
+                ))
+            }
+            Some(_) => {}
+        }
+    }
+    mismatches
+}
+
+/// Whether the shaders read the index of the view they render, for multiview (e.g. stereo)
+/// rendering.
+///
+/// The runner can't render more than one view: this version of wgpu has neither
+/// `Features::MULTIVIEW` nor the `multiview` count of render pipelines, which only arrive in later
+/// versions. Once it does, shaders using the view index should get a pipeline rendering to as many
+/// array layers, wherever the adapter supports the feature. Until then they're run single-view,
+/// and warned about.
+fn uses_view_index(reflection: &Reflection) -> bool {
+    reflection.built_ins.contains(&BuiltIn::ViewIndex)
+        || reflection.capabilities.contains(&Capability::MultiView)
+}
+
+/// Creates the pipeline for `shader_binary`, along with a vertex buffer for it if it reads any
+/// vertex attributes.
+///
+/// The vertex buffer layout is generated from the vertex shader's inputs, as reflected from the
+/// module. The runner has no actual vertex data to fill it with though, so the buffer is all
+/// zeros, which is reported along with any mismatches between the two shaders' interfaces.
 fn create_pipeline(
     device: &wgpu::Device,
     pipeline_layout: &wgpu::PipelineLayout,
     swapchain_format: wgpu::TextureFormat,
     shader_binary: wgpu::ShaderModuleDescriptor<'_>,
-) -> wgpu::RenderPipeline {
-    let module = device.create_shader_module(&shader_binary);
-    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+) -> (wgpu::RenderPipeline, Option<wgpu::Buffer>) {
+    let reflection = match &shader_binary.source {
+        wgpu::ShaderSource::SpirV(spirv) => Reflection::new(spirv).ok(),
+        // WGSL can't be reflected.
+        wgpu::ShaderSource::Wgsl(_) => None,
+    };
+    let vertex_entry_point = reflection
+        .as_ref()
+        .and_then(|reflection| reflection.entry_point(shaders::main_vs, ExecutionModel::Vertex));
+    let fragment_entry_point = reflection
+        .as_ref()
+        .and_then(|reflection| reflection.entry_point(shaders::main_fs, ExecutionModel::Fragment));
+    if let (Some(vertex), Some(fragment)) = (vertex_entry_point, fragment_entry_point) {
+        for mismatch in interface_mismatches(vertex, fragment) {
+            eprintln!("Warning: {}", mismatch);
+        }
+    }
+    if reflection.as_ref().map_or(false, uses_view_index) {
+        eprintln!(
+            "Warning: the shaders read `view_index`, but the runner renders a single view, so \
+             it's always 0"
+        );
+    }
+    let (array_stride, attributes) = match vertex_entry_point.map(vertex_attributes) {
+        Some(Ok(layout)) => layout,
+        Some(Err(err)) => {
+            eprintln!("Warning: can't generate a vertex buffer layout: {}", err);
+            (0, Vec::new())
+        }
+        None => (0, Vec::new()),
+    };
+    let vertex_buffer = if attributes.is_empty() {
+        None
+    } else {
+        eprintln!(
+            "Warning: {} reads vertex attributes at locations {:?}, which are all zero as the \
+             runner has no vertex data",
+            shaders::main_vs,
+            attributes
+                .iter()
+                .map(|attribute| attribute.shader_location)
+                .collect::<Vec<_>>()
+        );
+        Some(device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Vertex buffer"),
+            size: array_stride * wgpu::BufferAddress::from(VERTEX_COUNT),
+            usage: wgpu::BufferUsage::VERTEX,
+            // wgpu zero-initializes buffers that aren't mapped at creation.
+            mapped_at_creation: false,
+        }))
+    };
+    let vertex_buffers = if vertex_buffer.is_some() {
+        vec![wgpu::VertexBufferLayout {
+            array_stride,
+            step_mode: wgpu::InputStepMode::Vertex,
+            attributes: &attributes,
+        }]
+    } else {
+        Vec::new()
+    };
+
+    let module = crate::create_shader_module(device, &shader_binary)
+        .unwrap_or_else(|err| panic!("wgpu rejected the shader module: {}", err));
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
         label: None,
         layout: Some(pipeline_layout),
         vertex: wgpu::VertexState {
             module: &module,
             entry_point: shaders::main_vs,
-            buffers: &[],
+            buffers: &vertex_buffers,
         },
         primitive: wgpu::PrimitiveState {
             topology: wgpu::PrimitiveTopology::TriangleList,
@@ -305,12 +529,107 @@ fn create_pipeline(
                 write_mask: wgpu::ColorWrite::ALL,
             }],
         }),
-    })
+    });
+    (pipeline, vertex_buffer)
+}
+
+/// Without `--backends`, the graphics runner sticks to the backends it has always used.
+fn backends(options: &Options) -> wgpu::BackendBit {
+    options
+        .backends
+        .unwrap_or(wgpu::BackendBit::VULKAN | wgpu::BackendBit::METAL)
+}
+
+/// The size of the frame [`render_offscreen`] renders.
+#[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+const OFFSCREEN_SIZE: u32 = 256;
+
+/// Renders a single frame of `shader_binary` to a texture rather than a window, for checking
+/// that a graphics shader works where there's no window to open. The frame isn't read back.
+#[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+pub(crate) async fn render_offscreen(
+    options: &Options,
+    shader_binary: wgpu::ShaderModuleDescriptor<'static>,
+) -> Result<(), crate::RunnerError> {
+    let instance = wgpu::Instance::new(backends(options));
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            compatible_surface: None,
+        })
+        .await
+        .ok_or(crate::RunnerError::NoAdapter)?;
+    let features = requested_features(options);
+    check_features(&adapter, features)?;
+    let (device, queue) = adapter
+        .request_device(&device_descriptor(features), None)
+        .await
+        .map_err(crate::RunnerError::RequestDevice)?;
+
+    let format = options.surface_format.unwrap_or_else(swapchain_format);
+    let pipeline_layout = create_pipeline_layout(&device);
+    let (render_pipeline, vertex_buffer) =
+        create_pipeline(&device, &pipeline_layout, format, shader_binary);
+    let target = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Offscreen target"),
+        size: wgpu::Extent3d {
+            width: OFFSCREEN_SIZE,
+            height: OFFSCREEN_SIZE,
+            depth: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
+    });
+    let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let mut encoder =
+        device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    {
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[wgpu::RenderPassColorAttachmentDescriptor {
+                attachment: &view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::GREEN),
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+        let push_constants = ShaderConstants {
+            width: OFFSCREEN_SIZE,
+            height: OFFSCREEN_SIZE,
+            time: 0.0,
+            cursor_x: 0.0,
+            cursor_y: 0.0,
+            drag_start_x: 0.0,
+            drag_start_y: 0.0,
+            drag_end_x: 0.0,
+            drag_end_y: 0.0,
+            mouse_button_pressed: 0,
+            mouse_button_press_time: [f32::NEG_INFINITY; 3],
+        };
+        rpass.set_pipeline(&render_pipeline);
+        if let Some(vertex_buffer) = &vertex_buffer {
+            rpass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        }
+        rpass.set_push_constants(wgpu::ShaderStage::all(), 0, unsafe {
+            any_as_u8_slice(&push_constants)
+        });
+        rpass.draw(0..VERTEX_COUNT, 0..1);
+    }
+    queue.submit(Some(encoder.finish()));
+    device.poll(wgpu::Maintain::Wait);
+    Ok(())
 }
 
 pub fn start(options: &Options) {
     // Build the shader before we pop open a window, since it might take a while.
-    let rx = maybe_watch(options.shader, false);
+    let rx = maybe_watch(options, false);
     let initial_shader = rx.recv().expect("Initial shader is required");
 
     let event_loop = EventLoop::with_user_event();
@@ -325,6 +644,9 @@ pub fn start(options: &Options) {
         }
     });
     std::mem::forget(thread);
+    let backends = backends(options);
+    let present_mode = options.present_mode.unwrap_or(wgpu::PresentMode::Mailbox);
+    let features = requested_features(options);
     let window = winit::window::WindowBuilder::new()
         .with_title("Rust GPU - wgpu")
         .with_inner_size(winit::dpi::LogicalSize::new(1280.0, 720.0))
@@ -345,26 +667,58 @@ pub fn start(options: &Options) {
                         .ok()
                 })
                 .expect("couldn't append canvas to document body");
-            // Temporarily avoid srgb formats for the swapchain on the web
             wasm_bindgen_futures::spawn_local(run(
                 event_loop,
                 window,
-                wgpu::TextureFormat::Bgra8Unorm,
+                options.surface_format,
                 initial_shader,
+                backends,
+                present_mode,
+                features,
             ));
         } else {
-            wgpu_subscriber::initialize_default_subscriber(None);
             futures::executor::block_on(run(
                 event_loop,
                 window,
-                if cfg!(target_os = "android") {
-                    wgpu::TextureFormat::Rgba8UnormSrgb
-                } else {
-                    wgpu::TextureFormat::Bgra8UnormSrgb
-                },
+                options.surface_format,
                 initial_shader,
-
+                backends,
+                present_mode,
+                features,
             ));
         }
     }
 }
+
+/// The sRGB version of the surface's `preferred` format, so shaders' linear output is encoded for
+/// display, unless it has none. The web avoids sRGB formats, see [`swapchain_format`].
+fn preferred_srgb(preferred: wgpu::TextureFormat) -> wgpu::TextureFormat {
+    match preferred {
+        _ if cfg!(target_arch = "wasm32") => preferred,
+        wgpu::TextureFormat::Bgra8Unorm => wgpu::TextureFormat::Bgra8UnormSrgb,
+        wgpu::TextureFormat::Rgba8Unorm => wgpu::TextureFormat::Rgba8UnormSrgb,
+        _ => preferred,
+    }
+}
+
+/// The format of the swapchain the shaders render to, when there's no surface to ask for its
+/// preferred format, and no `--surface-format`: offscreen, and on Android before it's resumed.
+///
+/// This version of wgpu can't be asked which formats an adapter can render to or copy from
+/// (`Adapter::get_texture_format_features` only arrives in later versions), so the format is
+/// picked per platform instead, from those every adapter there is known to present:
+/// - `Bgra8UnormSrgb` on desktop,
+/// - `Rgba8UnormSrgb` on Android,
+/// - `Bgra8Unorm` on the web, temporarily avoiding sRGB formats there.
+///
+/// There's no screenshot mode reading the swapchain back yet. Once there is, and wgpu can report
+/// format features, this should check for `COPY_SRC` and fall back to the non-sRGB formats.
+fn swapchain_format() -> wgpu::TextureFormat {
+    if cfg!(target_arch = "wasm32") {
+        wgpu::TextureFormat::Bgra8Unorm
+    } else if cfg!(target_os = "android") {
+        wgpu::TextureFormat::Rgba8UnormSrgb
+    } else {
+        wgpu::TextureFormat::Bgra8UnormSrgb
+    }
+}
@@ -0,0 +1,279 @@
+//! Checks that host structs agree with the layout a shader expects of the buffers they're
+//! uploaded to, catching padding mismatches before they turn into garbage uniforms, and pads
+//! arrays of them out to the stride the shader expects.
+
+use crate::reflect::Reflection;
+use rspirv::binary::ParseState;
+use std::fmt;
+
+/// The fields of a `#[repr(C)]` host struct, as implemented by [`host_layout!`].
+pub trait HostLayout {
+    /// The name and byte offset of each field, in declaration order.
+    fn fields() -> Vec<(&'static str, usize)>;
+}
+
+/// Implements [`HostLayout`] for a struct, listing all of its fields in declaration order:
+///
+/// ```ignore
+/// host_layout!(Uniforms { time, resolution, cursor });
+/// ```
+#[macro_export]
+macro_rules! host_layout {
+    ($ty:ty { $($field:ident),* $(,)? }) => {
+        impl $crate::layout::HostLayout for $ty {
+            fn fields() -> Vec<(&'static str, usize)> {
+                let uninit = core::mem::MaybeUninit::<$ty>::uninit();
+                let base = uninit.as_ptr();
+                vec![$((
+                    stringify!($field),
+                    // SAFETY: `addr_of!` computes the field's address without reading it.
+                    unsafe { core::ptr::addr_of!((*base).$field) } as usize - base as usize,
+                )),*]
+            }
+        }
+    };
+}
+
+/// Types that are nothing but plain bytes: without padding of their own, and valid for any bit
+/// pattern, e.g. `#[repr(C)]` structs of `u32`s and `f32`s. Implement it for host structs to
+/// [`upload_structs`] and [`download_structs`] them.
+///
+/// # Safety
+///
+/// The type must have no padding bytes, and every bit pattern of its size must be a valid value
+/// of it.
+pub unsafe trait Plain: Copy {}
+
+unsafe impl Plain for u32 {}
+unsafe impl Plain for i32 {}
+unsafe impl Plain for f32 {}
+unsafe impl<T: Plain, const N: usize> Plain for [T; N] {}
+
+#[derive(Debug)]
+pub struct FieldMismatch {
+    pub field: &'static str,
+    pub host_offset: usize,
+    /// The name the shader gives the member, if it has debug names.
+    pub shader_name: Option<String>,
+    /// `None` if the shader's struct has no member for this field.
+    pub shader_offset: Option<u32>,
+}
+
+#[derive(Debug)]
+pub enum LayoutError {
+    InvalidSpirv(ParseState),
+    /// Nothing is bound at the given binding of descriptor set 0.
+    MissingBinding(u32),
+    Mismatch {
+        binding: u32,
+        fields: Vec<FieldMismatch>,
+        /// The number of members of the shader's struct, when it differs from the host's.
+        shader_members: Option<usize>,
+    },
+    /// The binding doesn't end in a runtime-sized array, or its stride isn't decorated.
+    NoArrayStride(u32),
+    /// The elements of the binding's runtime-sized array are `shader` bytes apart, but the host
+    /// type is `host` bytes in size, or for [`upload_structs`], larger than that.
+    StrideMismatch {
+        binding: u32,
+        shader: u64,
+        host: usize,
+    },
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayoutError::InvalidSpirv(err) => write!(f, "Shader is not valid SPIR-V: {:?}", err),
+            LayoutError::MissingBinding(binding) => {
+                write!(f, "Shader has nothing bound at binding {}", binding)
+            }
+            LayoutError::Mismatch {
+                binding,
+                fields,
+                shader_members,
+            } => {
+                write!(f, "Host layout doesn't match binding {}", binding)?;
+                if let Some(shader_members) = shader_members {
+                    write!(f, ", which has {} members", shader_members)?;
+                }
+                for field in fields {
+                    write!(f, "\n  {}: host offset {}", field.field, field.host_offset)?;
+                    match (&field.shader_name, field.shader_offset) {
+                        (Some(name), Some(offset)) => {
+                            write!(f, ", shader offset {} (`{}`)", offset, name)?
+                        }
+                        (None, Some(offset)) => write!(f, ", shader offset {}", offset)?,
+                        (_, None) => f.write_str(", missing from the shader")?,
+                    }
+                }
+                Ok(())
+            }
+            LayoutError::NoArrayStride(binding) => write!(
+                f,
+                "Binding {} has no runtime-sized array with a known stride",
+                binding
+            ),
+            LayoutError::StrideMismatch {
+                binding,
+                shader,
+                host,
+            } => write!(
+                f,
+                "Binding {} expects elements {} bytes apart, but the host type is {} bytes",
+                binding, shader, host
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LayoutError {}
+
+/// Checks that the fields of `T` are at the offsets the shader in `spirv` expects of the struct
+/// bound at `binding` of descriptor set 0, comparing them member by member in order.
+///
+/// Mismatches panic in debug builds, listing every field that's off, and are returned as an
+/// error otherwise.
+pub fn assert_layout_matches<T: HostLayout>(
+    spirv: &[u32],
+    binding: u32,
+) -> Result<(), LayoutError> {
+    let result = check_layout::<T>(spirv, binding);
+    if cfg!(debug_assertions) {
+        if let Err(err @ LayoutError::Mismatch { .. }) = &result {
+            panic!("{}", err);
+        }
+    }
+    result
+}
+
+/// Checks that an array of `T` has the stride the shader in `spirv` expects of the runtime-sized
+/// array bound at `binding` of descriptor set 0, i.e. that `T` is as large as the shader's
+/// elements, padding included. Use [`assert_layout_matches`] to check the fields of `T` as well.
+///
+/// Mismatches panic in debug builds, and are returned as an error otherwise.
+pub fn assert_stride_matches<T>(spirv: &[u32], binding: u32) -> Result<(), LayoutError> {
+    let result = check_stride::<T>(spirv, binding);
+    if cfg!(debug_assertions) {
+        if let Err(err @ LayoutError::StrideMismatch { .. }) = &result {
+            panic!("{}", err);
+        }
+    }
+    result
+}
+
+/// Lays `elements` out as the shader in `spirv` expects the runtime-sized array bound at `binding`
+/// of descriptor set 0, each followed by zeroed padding up to the array's stride, e.g. a 12-byte
+/// struct of three `f32`s padded to 16 bytes under std430.
+///
+/// Fails with [`LayoutError::StrideMismatch`] if `T` is larger than the stride, which would make
+/// its elements overlap. Use [`assert_layout_matches`] to check the fields of `T` as well.
+pub fn upload_structs<T: Plain>(
+    elements: &[T],
+    spirv: &[u32],
+    binding: u32,
+) -> Result<Vec<u8>, LayoutError> {
+    let stride = padded_stride::<T>(spirv, binding)?;
+    let mut bytes = vec![0; elements.len() * stride];
+    for (element, padded) in elements.iter().zip(bytes.chunks_exact_mut(stride)) {
+        // SAFETY: `Plain` types have no padding, so every byte of `element` is initialized.
+        let element = unsafe {
+            std::slice::from_raw_parts(element as *const T as *const u8, std::mem::size_of::<T>())
+        };
+        padded[..element.len()].copy_from_slice(element);
+    }
+    Ok(bytes)
+}
+
+/// The elements of the runtime-sized array bound at `binding` of descriptor set 0 of the shader in
+/// `spirv`, read back as `bytes`, without the padding [`upload_structs`] adds. Bytes after the
+/// last whole element are ignored.
+pub fn download_structs<T: Plain>(
+    bytes: &[u8],
+    spirv: &[u32],
+    binding: u32,
+) -> Result<Vec<T>, LayoutError> {
+    let stride = padded_stride::<T>(spirv, binding)?;
+    Ok(bytes
+        .chunks_exact(stride)
+        // SAFETY: `Plain` types are valid for any bits, and each chunk holds at least a `T`,
+        // which `read_unaligned` reads regardless of the chunk's alignment.
+        .map(|padded| unsafe { std::ptr::read_unaligned(padded.as_ptr() as *const T) })
+        .collect())
+}
+
+/// The stride of the runtime-sized array bound at `binding`, checked to fit a `T`.
+fn padded_stride<T>(spirv: &[u32], binding: u32) -> Result<usize, LayoutError> {
+    let shader = array_stride(spirv, binding)?;
+    let host = std::mem::size_of::<T>();
+    if host as u64 <= shader {
+        Ok(shader as usize)
+    } else {
+        Err(LayoutError::StrideMismatch {
+            binding,
+            shader,
+            host,
+        })
+    }
+}
+
+/// The stride the shader in `spirv` reflects for the runtime-sized array bound at `binding`.
+fn array_stride(spirv: &[u32], binding: u32) -> Result<u64, LayoutError> {
+    let reflection = Reflection::new(spirv).map_err(LayoutError::InvalidSpirv)?;
+    reflection
+        .binding(0, binding)
+        .ok_or(LayoutError::MissingBinding(binding))?
+        .element_size
+        .filter(|&stride| stride > 0)
+        .ok_or(LayoutError::NoArrayStride(binding))
+}
+
+fn check_stride<T>(spirv: &[u32], binding: u32) -> Result<(), LayoutError> {
+    let shader = array_stride(spirv, binding)?;
+    let host = std::mem::size_of::<T>();
+    if shader == host as u64 {
+        Ok(())
+    } else {
+        Err(LayoutError::StrideMismatch {
+            binding,
+            shader,
+            host,
+        })
+    }
+}
+
+fn check_layout<T: HostLayout>(spirv: &[u32], binding: u32) -> Result<(), LayoutError> {
+    let reflection = Reflection::new(spirv).map_err(LayoutError::InvalidSpirv)?;
+    let members = &reflection
+        .binding(0, binding)
+        .ok_or(LayoutError::MissingBinding(binding))?
+        .members;
+    let host_fields = T::fields();
+    let fields = host_fields
+        .iter()
+        .enumerate()
+        .filter_map(|(index, &(field, host_offset))| {
+            let member = members.get(index);
+            let shader_offset = member.and_then(|member| member.offset);
+            if shader_offset == Some(host_offset as u32) {
+                return None;
+            }
+            Some(FieldMismatch {
+                field,
+                host_offset,
+                shader_name: member.and_then(|member| member.name.clone()),
+                shader_offset,
+            })
+        })
+        .collect::<Vec<_>>();
+    let shader_members = Some(members.len()).filter(|&len| len != host_fields.len());
+    if fields.is_empty() && shader_members.is_none() {
+        Ok(())
+    } else {
+        Err(LayoutError::Mismatch {
+            binding,
+            fields,
+            shader_members,
+        })
+    }
+}
@@ -42,15 +42,57 @@
     rust_2018_idioms
 )]
 
+use std::convert::TryInto;
+use std::fmt;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::{self, Receiver};
 
 use clap::Clap;
-use strum::{Display, EnumString};
+use strum::{Display, EnumIter, EnumString};
 
 mod compute;
 mod graphics;
+pub mod layout;
+#[cfg(not(target_arch = "wasm32"))]
+mod output_file;
+#[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+mod rebuild;
+mod reflect;
+#[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+mod report;
+#[cfg(all(
+    feature = "run-compute",
+    not(any(target_os = "android", target_arch = "wasm32"))
+))]
+mod run_compute;
+#[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+mod smoke;
+mod spirv_module;
+#[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+mod spv_diff;
+mod texture;
+mod trace;
+#[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+mod validate;
 
-#[derive(EnumString, Display, PartialEq, Copy, Clone)]
+#[cfg(not(target_arch = "wasm32"))]
+pub use compute::spawn_compute;
+pub use compute::{
+    poll_once, start_with_generator, try_start, ComputeContext, ComputeOutput, DebugRecords,
+    Readback, RunnerError, Submission, TimestampGranularity, COUNT_BINDING, DEBUG_BINDING,
+};
+pub use reflect::{used_capabilities, SourceLocation};
+#[cfg(all(
+    feature = "run-compute",
+    not(any(target_os = "android", target_arch = "wasm32"))
+))]
+pub use run_compute::{pack_elements, run_compute_crate, unpack_elements, ComputeElement};
+pub use spirv_module::SpirvModule;
+#[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+pub use spv_diff::diff_spirv;
+
+#[derive(EnumString, EnumIter, Display, PartialEq, Copy, Clone)]
 pub enum RustGPUShader {
     Simplest,
     Sky,
@@ -58,8 +100,125 @@ pub enum RustGPUShader {
     Mouse,
 }
 
+/// How much diagnostic output to print, from `--quiet` and `-v`.
+#[derive(PartialEq, PartialOrd, Copy, Clone)]
+pub enum Verbosity {
+    /// Only errors and the final result.
+    Quiet,
+    Normal,
+    Verbose,
+    Debug,
+}
+
+impl Verbosity {
+    /// The `RUST_LOG` filter matching this verbosity.
+    fn log_filter(self) -> &'static str {
+        match self {
+            Verbosity::Quiet => "error",
+            Verbosity::Normal => "warn",
+            Verbosity::Verbose => "info",
+            Verbosity::Debug => "debug",
+        }
+    }
+}
+
+/// How the contents of the storage buffer are interpreted after a compute dispatch.
+#[derive(EnumString, Display, PartialEq, Copy, Clone)]
+#[strum(serialize_all = "lowercase")]
+pub enum OutputType {
+    /// Collatz sequence lengths, as computed by the compute shader.
+    U32,
+    /// Floats, summarized by their range, mean and number of NaNs.
+    F32,
+}
+
+/// The byte order of the `u32`s in `--input` and `--output` files.
+#[derive(EnumString, Display, PartialEq, Copy, Clone)]
+#[strum(serialize_all = "lowercase")]
+pub enum Endian {
+    /// Little-endian, as SPIR-V itself is.
+    Le,
+    Be,
+    /// Whatever the host uses, with the bytes copied as they are.
+    Native,
+}
+
+/// The filtering used when sampling the `--texture` of a compute shader.
+#[derive(EnumString, Display, PartialEq, Copy, Clone)]
+pub enum Filter {
+    Nearest,
+    Linear,
+}
+
+/// The wgpu backends selected by `--backends`, or the platform's primary backends.
+fn backends(options: &Options) -> wgpu::BackendBit {
+    options.backends.unwrap_or(wgpu::BackendBit::PRIMARY)
+}
+
+/// Called with the result of every successful shader build, including rebuilds in watch mode.
+#[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+pub type OnRebuild = std::sync::Arc<dyn Fn(&spirv_builder::CompileResult) + Send + Sync>;
+
+/// The directory of the crate of `shader`.
+#[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+fn shader_crate_path(shader: RustGPUShader) -> PathBuf {
+    let crate_name = match shader {
+        RustGPUShader::Simplest => "simplest-shader",
+        RustGPUShader::Sky => "sky-shader",
+        RustGPUShader::Compute => "compute-shader",
+        RustGPUShader::Mouse => "mouse-shader",
+    };
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    [manifest_dir, "..", "..", "shaders", crate_name]
+        .iter()
+        .copied()
+        .collect::<PathBuf>()
+}
+
+/// A builder for the crate of `shader`, configured as `options` asks.
+#[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+fn shader_builder(options: &Options, shader: RustGPUShader) -> spirv_builder::SpirvBuilder {
+    use spirv_builder::Capability;
+    let capabilities: &[Capability] = match shader {
+        RustGPUShader::Compute => &[Capability::Int8],
+        RustGPUShader::Simplest | RustGPUShader::Sky | RustGPUShader::Mouse => &[],
+    };
+    let mut builder = crate_builder(options, shader_crate_path(shader));
+    for &cap in capabilities {
+        builder = builder.capability(cap);
+    }
+    builder
+}
+
+/// The target the shader crates are built for.
+#[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+const SHADER_TARGET: &str = "spirv-unknown-vulkan1.1";
+
+/// A builder for the shader crate in `path`, configured as `options` asks.
+#[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+fn crate_builder(options: &Options, path: impl AsRef<Path>) -> spirv_builder::SpirvBuilder {
+    use spirv_builder::{MetadataPrintout, SpirvBuilder};
+    // Hack: spirv_builder builds into a custom directory if running under cargo, to not
+    // deadlock, and the default target directory if not. However, packages like `proc-macro2`
+    // have different configurations when being built here vs. when building
+    // rustc_codegen_spirv normally, so we *want* to build into a separate target directory, to
+    // not have to rebuild half the crate graph every time we run. So, pretend we're running
+    // under cargo by setting these environment variables.
+    std::env::set_var("OUT_DIR", env!("OUT_DIR"));
+    std::env::set_var("PROFILE", env!("PROFILE"));
+    let mut builder = SpirvBuilder::new(path, SHADER_TARGET)
+        .print_metadata(MetadataPrintout::None)
+        .shader_crate_features(&options.shader_features)
+        .time_entry_points(options.time_entry_points)
+        .print_command(options.print_build_command);
+    if cfg!(feature = "debug-printf") {
+        builder = builder.extension("SPV_KHR_non_semantic_info");
+    }
+    builder
+}
+
 fn maybe_watch(
-    shader: RustGPUShader,
+    options: &Options,
     force_no_watch: bool,
 ) -> Receiver<wgpu::ShaderModuleDescriptor<'static>> {
     // This bound needs to be 1, because in cases where this function is used for direct building (e.g. for the compute example or on android)
@@ -67,72 +226,52 @@ fn maybe_watch(
     let (tx, rx) = mpsc::sync_channel(1);
     #[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
     {
-        use spirv_builder::{Capability, CompileResult, MetadataPrintout, SpirvBuilder};
-        use std::borrow::Cow;
-        use std::path::PathBuf;
-        // Hack: spirv_builder builds into a custom directory if running under cargo, to not
-        // deadlock, and the default target directory if not. However, packages like `proc-macro2`
-        // have different configurations when being built here vs. when building
-        // rustc_codegen_spirv normally, so we *want* to build into a separate target directory, to
-        // not have to rebuild half the crate graph every time we run. So, pretend we're running
-        // under cargo by setting these environment variables.
-        std::env::set_var("OUT_DIR", env!("OUT_DIR"));
-        std::env::set_var("PROFILE", env!("PROFILE"));
-        let (crate_name, capabilities): (_, &[Capability]) = match shader {
-            RustGPUShader::Simplest => ("simplest-shader", &[]),
-            RustGPUShader::Sky => ("sky-shader", &[]),
-            RustGPUShader::Compute => ("compute-shader", &[Capability::Int8]),
-            RustGPUShader::Mouse => ("mouse-shader", &[]),
-        };
-        let manifest_dir = env!("CARGO_MANIFEST_DIR");
-        let crate_path = [manifest_dir, "..", "..", "shaders", crate_name]
-            .iter()
-            .copied()
-            .collect::<PathBuf>();
-        let mut builder = SpirvBuilder::new(crate_path, "spirv-unknown-vulkan1.1")
-            .print_metadata(MetadataPrintout::None);
-        for &cap in capabilities {
-            builder = builder.capability(cap);
-        }
+        use spirv_builder::CompileResult;
+        let builder = shader_builder(options, options.shader);
+        let on_rebuild = options.on_rebuild.clone();
         if force_no_watch {
-            let compile_result = builder.build().unwrap();
-            handle_builder_result(compile_result, &tx);
+            // On failure, `tx` is dropped without sending, which the receiver sees as an error.
+            if let Ok(compile_result) = builder.build() {
+                handle_builder_result(compile_result, &tx, on_rebuild.as_ref());
+            }
         } else {
             let thread = std::thread::spawn(move || {
-                builder
-                    .watch(|compile_result| {
-                        handle_builder_result(compile_result, &tx);
-                    })
-                    .expect("Configuration is correct for watching")
+                let result = builder.watch(|compile_result| {
+                    handle_builder_result(compile_result, &tx, on_rebuild.as_ref());
+                });
+                // Returning drops `tx`, which the receiver sees as the build having failed.
+                if let Err(err) = result {
+                    eprintln!("Failed to watch the shader: {}", err);
+                }
             });
             std::mem::forget(thread);
         }
         fn handle_builder_result(
             compile_result: CompileResult,
             tx: &mpsc::SyncSender<wgpu::ShaderModuleDescriptor<'static>>,
+            on_rebuild: Option<&OnRebuild>,
         ) {
+            if let Some(on_rebuild) = on_rebuild {
+                on_rebuild(&compile_result);
+            }
+            if !compile_result.timings.is_empty() {
+                eprint!("{}", compile_result.timings_table());
+            }
             let module_path = compile_result.module.unwrap_single();
-            let data = std::fs::read(module_path).unwrap();
-            let spirv = wgpu::util::make_spirv(&data);
-            let spirv = match spirv {
-                wgpu::ShaderSource::Wgsl(cow) => {
-                    wgpu::ShaderSource::Wgsl(Cow::Owned(cow.into_owned()))
-                }
-                wgpu::ShaderSource::SpirV(cow) => {
-                    wgpu::ShaderSource::SpirV(Cow::Owned(cow.into_owned()))
+            let spirv = match read_spirv(module_path) {
+                Ok(spirv) => spirv,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    return;
                 }
             };
-            tx.send(wgpu::ShaderModuleDescriptor {
-                label: None,
-                source: spirv,
-                flags: wgpu::ShaderFlags::default(),
-            })
-            .expect("Rx is still alive");
+            // The receiver is only gone once it's no longer interested in new shaders.
+            let _ = tx.send(SpirvModule::new(spirv).into_descriptor());
         }
     }
     #[cfg(any(target_os = "android", target_arch = "wasm32"))]
     {
-        tx.send(match shader {
+        tx.send(match options.shader {
             RustGPUShader::Simplest => wgpu::include_spirv!(env!("simplest_shader.spv")),
             RustGPUShader::Sky => wgpu::include_spirv!(env!("sky_shader.spv")),
             RustGPUShader::Compute => wgpu::include_spirv!(env!("compute_shader.spv")),
@@ -143,6 +282,123 @@ fn maybe_watch(
     rx
 }
 
+/// Reads the SPIR-V module at `path`, in whichever endianness it was written.
+fn read_spirv(path: &Path) -> Result<Vec<u32>, String> {
+    let bytes = std::fs::read(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    if bytes.len() % 4 != 0 {
+        return Err(format!(
+            "{}: length {} is not a multiple of 4",
+            path.display(),
+            bytes.len()
+        ));
+    }
+    let mut words = bytes
+        .chunks_exact(4)
+        .map(|b| u32::from_ne_bytes(b.try_into().unwrap()))
+        .collect::<Vec<_>>();
+    match words.first() {
+        Some(&rspirv::spirv::MAGIC_NUMBER) => {}
+        Some(&magic) if magic == rspirv::spirv::MAGIC_NUMBER.swap_bytes() => {
+            for word in &mut words {
+                *word = word.swap_bytes();
+            }
+        }
+        _ => return Err(format!("{}: not a SPIR-V module", path.display())),
+    }
+    Ok(words)
+}
+
+/// Runs `create`, returning wgpu's full validation errors if it raises any meanwhile, one per
+/// line, instead of letting wgpu panic with them.
+///
+/// This version of wgpu has no error scopes, so the uncaptured error handler stands in for one: it
+/// collects errors while `create` runs, and then goes back to panicking on them like wgpu's
+/// default handler. On the web errors are reported asynchronously, so they can't be captured.
+fn capture_errors<T>(device: &wgpu::Device, create: impl FnOnce() -> T) -> Result<T, String> {
+    if cfg!(target_arch = "wasm32") {
+        return Ok(create());
+    }
+    let errors = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let sink = errors.clone();
+    device.on_uncaptured_error(move |error| sink.lock().unwrap().push(error.to_string()));
+    let created = create();
+    device.on_uncaptured_error(|error| panic!("wgpu error: {}", error));
+    let errors = std::mem::take(&mut *errors.lock().unwrap());
+    if errors.is_empty() {
+        Ok(created)
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+/// Creates a shader module, returning wgpu's full validation error if it rejects the module,
+/// along with what reflection can tell about it, instead of letting wgpu panic with a summary.
+fn create_shader_module(
+    device: &wgpu::Device,
+    desc: &wgpu::ShaderModuleDescriptor<'_>,
+) -> Result<wgpu::ShaderModule, String> {
+    capture_errors(device, || device.create_shader_module(desc)).map_err(|mut report| {
+        if let wgpu::ShaderSource::SpirV(words) = &desc.source {
+            report.push('\n');
+            report.push_str(&describe_spirv(words));
+        }
+        report
+    })
+}
+
+/// Whether to hand SPIR-V shaders to the driver without naga validating them, with
+/// `--passthrough` on `backend`.
+///
+/// This version of wgpu has no `SPIRV_SHADER_PASSTHROUGH` feature: it always hands SPIR-V to the
+/// backend as is, and only parses it with naga to validate it, which clearing
+/// `ShaderFlags::VALIDATION` skips. That's only a true passthrough on Vulkan though, as the other
+/// backends translate the module with SPIRV-Cross regardless, so elsewhere naga still validates it.
+fn passthrough(options: &Options, backend: wgpu::Backend) -> bool {
+    options.passthrough && backend == wgpu::Backend::Vulkan
+}
+
+/// `desc`, without asking wgpu to validate the module with naga. See [`passthrough`].
+fn without_validation<'a>(
+    desc: &'a wgpu::ShaderModuleDescriptor<'_>,
+) -> wgpu::ShaderModuleDescriptor<'a> {
+    wgpu::ShaderModuleDescriptor {
+        label: desc.label,
+        source: match &desc.source {
+            wgpu::ShaderSource::SpirV(words) => {
+                wgpu::ShaderSource::SpirV(std::borrow::Cow::Borrowed(words))
+            }
+            wgpu::ShaderSource::Wgsl(source) => {
+                wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(source))
+            }
+        },
+        flags: desc.flags - wgpu::ShaderFlags::VALIDATION,
+    }
+}
+
+/// Summarizes the entry points and capabilities of a SPIR-V module, to relate a validation error
+/// back to the shader.
+fn describe_spirv(words: &[u32]) -> String {
+    let reflection = match reflect::Reflection::new(words) {
+        Ok(reflection) => reflection,
+        Err(err) => return format!("The module isn't valid SPIR-V either: {:?}", err),
+    };
+    let entry_points = reflection
+        .entry_points
+        .iter()
+        .map(|entry_point| format!("{} ({:?})", entry_point.name, entry_point.execution_model))
+        .collect::<Vec<_>>();
+    let capabilities = reflection
+        .capabilities
+        .iter()
+        .map(|capability| format!("{:?}", capability))
+        .collect::<Vec<_>>();
+    format!(
+        "The module has entry points {}, and declares capabilities {}",
+        entry_points.join(", "),
+        capabilities.join(", ")
+    )
+}
+
 fn is_compute_shader(shader: RustGPUShader) -> bool {
     shader == RustGPUShader::Compute
 }
@@ -151,15 +407,970 @@ fn is_compute_shader(shader: RustGPUShader) -> bool {
 pub struct Options {
     #[clap(short, long, default_value = "Sky")]
     shader: RustGPUShader,
+
+    /// Only print errors and the final result.
+    #[clap(short, long)]
+    quiet: bool,
+
+    /// Print more diagnostics, and more still when repeated (`-vv`).
+    #[clap(short, long, parse(from_occurrences))]
+    verbose: u8,
+
+    /// Build every shader crate and report the entry points, capabilities and resource bindings
+    /// of each, instead of running a shader.
+    #[clap(long)]
+    report: bool,
+
+    /// Build `--shader` (or load the `--spv-file`) and print its interface: entry points, their
+    /// inputs and outputs, resource bindings, push constants and capabilities, as JSON with
+    /// `--json`, instead of running it.
+    #[clap(long)]
+    describe: bool,
+
+    /// Build `--shader`, then touch its source and time the rebuild done by watching it, to
+    /// compare full and incremental build times.
+    #[clap(long)]
+    time_rebuild: bool,
+
+    /// Print how long each function of the shader crate took to codegen, slowest first, after
+    /// each build.
+    #[clap(long)]
+    time_entry_points: bool,
+
+    /// Print the `cargo` command that builds the shader crate, with the directory it runs in and
+    /// the environment variables set for it, before each build, to reproduce a build by hand.
+    #[clap(long)]
+    print_build_command: bool,
+
+    /// Build and reflect the compute shader, and print the bindings, dispatch and buffer sizes
+    /// that running it would use, without touching the GPU.
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Build the shader (or load the `--spv-file`) and run `spirv-val` over it, printing its
+    /// diagnostics and failing if the module is invalid, without touching the GPU.
+    #[clap(long)]
+    validate_only: bool,
+
+    /// Print the compute results (or `--report` or `--describe`) as JSON instead of human
+    /// readable text.
+    #[clap(long)]
+    json: bool,
+
+    /// Print a hash of the compute results instead of the results themselves, for quickly
+    /// checking whether runs on different adapters or builds agree.
+    #[clap(long)]
+    hash: bool,
+
+    /// Keep re-running the compute shader, picking up changes to its source as they're made.
+    #[clap(long = "loop")]
+    keep_running: bool,
+
+    /// Prompt for new input ranges to re-run the compute shader with, reusing the same device.
+    #[clap(long)]
+    repl: bool,
+
+    /// The number of timed dispatches of the compute shader.
+    #[clap(long, default_value = "1")]
+    iterations: u32,
+
+    /// The number of untimed dispatches before the timed ones. Defaults to 3 when benchmarking
+    /// with `--iterations`, and 0 otherwise.
+    #[clap(long)]
+    warmup: Option<u32>,
+
+    /// Re-run the compute shader on the same device up to this many times, stopping with an error
+    /// as soon as a run's results differ from the first run's, to catch nondeterministic bugs.
+    #[clap(long)]
+    repeat_until_diff: Option<u32>,
+
+    /// How to interpret the compute results: `u32` for the Collatz sequence lengths, or `f32`
+    /// for statistics over floats from numerical kernels.
+    #[clap(long, default_value = "u32")]
+    output_type: OutputType,
+
+    /// Write the compute results to this PNG, interpreted as `--width` x `--height` RGBA8 pixels.
+    #[clap(long)]
+    image: Option<PathBuf>,
+
+    #[clap(long)]
+    width: Option<u32>,
+
+    #[clap(long)]
+    height: Option<u32>,
+
+    /// Compare the compute results, as a `--width` x `--height` image, against this PNG, failing
+    /// with a diff image next to it if they don't match.
+    #[clap(long)]
+    expect: Option<PathBuf>,
+
+    /// How much each channel of a pixel may differ from the `--expect` image.
+    #[clap(long, default_value = "2")]
+    tolerance: u8,
+
+    /// An 8-bit RGBA PNG to bind for compute shaders that sample a texture. Shaders sampling an
+    /// array of textures get one element per PNG given.
+    #[clap(long)]
+    texture: Vec<PathBuf>,
+
+    #[clap(long, default_value = "Linear")]
+    filter: Filter,
+
+    /// Give up on GPU work that takes longer than this many seconds.
+    #[clap(long)]
+    timeout: Option<u64>,
+
+    /// Read back compute results larger than this many bytes in chunks of at most this size, for
+    /// backends that limit how much can be mapped at once. Must be at least 4.
+    #[clap(long)]
+    max_map_size: Option<u64>,
+
+    /// Read the compute results back straight into this file, memory-mapped, rather than into
+    /// memory, for results too large to hold there. The file is sized to the results after
+    /// checking there's room for them, and they aren't printed, summarized or hashed.
+    #[clap(long)]
+    output_mmap: Option<PathBuf>,
+
+    /// Fail before creating the pipeline if the compute shader declares more than this many bytes
+    /// of workgroup shared memory, e.g. the device's `maxComputeSharedMemorySize`. Defaults to
+    /// 16384, the least every device supports.
+    #[clap(long)]
+    max_workgroup_storage: Option<u64>,
+
+    /// Bind a zeroed counter at binding 1 of the compute shader, and read back only that rather
+    /// than the results, e.g. `--entry main_count_cs --count-only` to count the inputs whose
+    /// Collatz sequences take at least 256 steps, however many inputs there are.
+    #[clap(long)]
+    count_only: bool,
+
+    /// Warn if the dispatch leaves the compute results, or any `--output` buffer, just as they
+    /// were uploaded, which usually means the shader never wrote to them, e.g. when running the
+    /// wrong entry point or declaring its buffers at other bindings.
+    #[clap(long)]
+    sanity: bool,
+
+    /// Write a timeline of the compute run to this file (e.g. `trace.json`), with the build,
+    /// setup, each stage of every dispatch, the readback and the GPU time of each timed dispatch,
+    /// for loading in `chrome://tracing` or Perfetto.
+    #[clap(long)]
+    trace: Option<PathBuf>,
+
+    /// Run compute shaders on the first adapter whose name contains this, ignoring case. Takes
+    /// precedence over `--power-pref` and the `WGPU_ADAPTER_NAME` environment variable.
+    #[clap(long)]
+    adapter: Option<String>,
+
+    /// Run compute shaders on a software adapter such as lavapipe or WARP, for results that are
+    /// the same on every machine. Takes precedence over `--adapter` and `--power-pref`.
+    #[clap(long)]
+    software: bool,
+
+    /// Hand compute shaders to the driver without validating them with naga, e.g. for SPIR-V
+    /// features naga doesn't handle yet. Only supported on Vulkan, elsewhere naga still validates
+    /// them.
+    #[clap(long)]
+    passthrough: bool,
+
+    /// Run the compute shader twice on the same device, validated with naga and then passed
+    /// through to the driver, and fail if the results differ, to tell naga translation bugs from
+    /// driver bugs. Only supported on Vulkan.
+    #[clap(long)]
+    compare_passthrough: bool,
+
+    /// Run compute shaders on a `low` power or `high` performance adapter. Takes precedence over
+    /// the `WGPU_ADAPTER_NAME` and `WGPU_POWER_PREF` environment variables.
+    #[clap(long, parse(try_from_str = parse_power_preference))]
+    power_pref: Option<wgpu::PowerPreference>,
+
+    /// Comma separated wgpu backends to look for adapters on, out of `vulkan`, `dx12`, `dx11`,
+    /// `metal`, `gl`, `webgpu`, `primary`, `secondary` and `all`, e.g. to reach the secondary
+    /// backends such as GL. Defaults to the primary backends of the platform.
+    #[clap(long, parse(try_from_str = parse_backends))]
+    backends: Option<wgpu::BackendBit>,
+
+    /// Present graphics shaders with the `fifo`, `mailbox` or `immediate` present mode, rather
+    /// than `mailbox`. Surfaces that don't support the mode fall back to `fifo`.
+    #[clap(long, parse(try_from_str = parse_present_mode))]
+    present_mode: Option<wgpu::PresentMode>,
+
+    /// Render graphics shaders to a surface of this format, out of `bgra8unorm-srgb`,
+    /// `bgra8unorm`, `rgba8unorm-srgb`, `rgba8unorm`, `rgba16float` and `rgb10a2unorm`, rather than
+    /// the sRGB version of the surface's preferred format.
+    #[clap(long, parse(try_from_str = parse_surface_format))]
+    surface_format: Option<wgpu::TextureFormat>,
+
+    /// Request a wgpu feature for the graphics device, by the name of its `wgpu::Features` flag,
+    /// e.g. `depth_clamping`. Can be given more than once, and fails if the adapter lacks any of
+    /// the features requested.
+    #[clap(long = "feature", parse(try_from_str = parse_feature))]
+    features: Vec<wgpu::Features>,
+
+    /// Dispatch a 2D or 3D grid of this many invocations, given as `x,y,z`, instead of one
+    /// invocation per element of the input.
+    #[clap(long, parse(try_from_str = parse_dims))]
+    dispatch_dims: Option<[u32; 3]>,
+
+    /// Fill the storage buffer with this value before every dispatch, after the input is copied
+    /// in, e.g. to reset the output of accumulation kernels.
+    #[clap(long)]
+    clear: Option<u32>,
+
+    /// The elements to `--clear`, given as `start..end`. Defaults to all of them.
+    #[clap(long, parse(try_from_str = parse_range))]
+    clear_range: Option<Range<u32>>,
+
+    /// Bind the raw contents of a file as the storage buffer at a binding of the compute shader,
+    /// given as `binding=path`. Binding 0 always holds the input range.
+    #[clap(long, parse(try_from_str = parse_buffer_file))]
+    input: Vec<BufferFile>,
+
+    /// Write the raw contents of the storage buffer at a binding to a file after the run, given
+    /// as `binding=path`.
+    #[clap(long, parse(try_from_str = parse_buffer_file))]
+    output: Vec<BufferFile>,
+
+    /// Bind an append buffer, given as `binding=capacity`: a `u32` cursor followed by room for
+    /// `capacity` elements, which the shader reserves by atomically incrementing the cursor. The
+    /// cursor starts at zero, and only the elements it counts are read back (and written to an
+    /// `--output` for the binding).
+    #[clap(long, parse(try_from_str = parse_append_buffer))]
+    append: Vec<AppendBuffer>,
+
+    /// The byte order of the `u32`s in `--input` files, and to write `--output` files in:
+    /// "le", "be" or "native". Files must be a whole number of `u32`s unless it's "native".
+    #[clap(long, default_value = "le")]
+    input_endian: Endian,
+
+    /// Comma separated cargo features to enable on the shader crate.
+    #[clap(long, use_delimiter = true)]
+    shader_features: Vec<String>,
+
+    /// Run this precompiled SPIR-V compute shader instead of building `--shader`.
+    #[clap(long)]
+    spv_file: Option<PathBuf>,
+
+    /// Print the record of the invocation at this flattened global invocation id from the debug
+    /// buffer the compute shader declares at binding 15, if any. Can be given more than once.
+    #[clap(long)]
+    debug_index: Vec<u64>,
+
+    /// The name of the compute entry point to run, e.g. `main_scan_cs` to find the running
+    /// maxima with a scan on the GPU.
+    #[clap(long, alias = "entry", default_value = "main_cs")]
+    entry_point: String,
+
+    #[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+    #[clap(skip)]
+    on_rebuild: Option<OnRebuild>,
+}
+
+/// Parses `x`, `x,y` or `x,y,z`, with missing dimensions defaulting to 1.
+fn parse_dims(s: &str) -> Result<[u32; 3], String> {
+    let mut dims = [1; 3];
+    let mut parts = s.split(',');
+    for dim in &mut dims {
+        if let Some(part) = parts.next() {
+            *dim = part
+                .trim()
+                .parse()
+                .map_err(|err| format!("invalid dimension `{}`: {}", part, err))?;
+        }
+    }
+    if parts.next().is_some() {
+        return Err(format!("expected at most 3 dimensions, got `{}`", s));
+    }
+    Ok(dims)
+}
+
+/// A file to fill a storage buffer from, or to write one to, for `--input` and `--output`.
+#[derive(Debug, Clone)]
+pub struct BufferFile {
+    pub binding: u32,
+    pub path: PathBuf,
+}
+
+/// A storage buffer with an atomic cursor counting the elements appended after it, for `--append`.
+#[derive(Debug, Clone)]
+pub struct AppendBuffer {
+    pub binding: u32,
+    /// How many elements there's room for after the cursor.
+    pub capacity: u32,
+}
+
+/// Parses `binding=capacity`.
+fn parse_append_buffer(s: &str) -> Result<AppendBuffer, String> {
+    let (binding, capacity) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected `binding=capacity`, got `{}`", s))?;
+    let binding = binding
+        .trim()
+        .parse()
+        .map_err(|err| format!("invalid binding `{}`: {}", binding, err))?;
+    let capacity = capacity
+        .trim()
+        .parse()
+        .map_err(|err| format!("invalid capacity `{}`: {}", capacity, err))?;
+    Ok(AppendBuffer { binding, capacity })
+}
+
+/// Parses `low` or `high`, like wgpu's own `WGPU_POWER_PREF`.
+fn parse_power_preference(s: &str) -> Result<wgpu::PowerPreference, String> {
+    match s.to_lowercase().as_str() {
+        "low" => Ok(wgpu::PowerPreference::LowPower),
+        "high" => Ok(wgpu::PowerPreference::HighPerformance),
+        _ => Err(format!("expected `low` or `high`, got `{}`", s)),
+    }
+}
+
+/// Parses `fifo`, `mailbox` or `immediate`.
+fn parse_present_mode(s: &str) -> Result<wgpu::PresentMode, String> {
+    match s.to_lowercase().as_str() {
+        "fifo" => Ok(wgpu::PresentMode::Fifo),
+        "mailbox" => Ok(wgpu::PresentMode::Mailbox),
+        "immediate" => Ok(wgpu::PresentMode::Immediate),
+        _ => Err(format!(
+            "expected `fifo`, `mailbox` or `immediate`, got `{}`",
+            s
+        )),
+    }
+}
+
+fn parse_feature(s: &str) -> Result<wgpu::Features, String> {
+    let name = s.to_uppercase().replace('-', "_");
+    let features = (0..64).filter_map(|bit| wgpu::Features::from_bits(1 << bit));
+    features
+        .clone()
+        .find(|feature| format!("{:?}", feature) == name)
+        .ok_or_else(|| {
+            let names = features
+                .map(|feature| format!("{:?}", feature).to_lowercase())
+                .collect::<Vec<_>>();
+            format!("expected one of {}, got `{}`", names.join(", "), s)
+        })
+}
+
+fn parse_surface_format(s: &str) -> Result<wgpu::TextureFormat, String> {
+    match s.to_lowercase().as_str() {
+        "bgra8unorm-srgb" => Ok(wgpu::TextureFormat::Bgra8UnormSrgb),
+        "bgra8unorm" => Ok(wgpu::TextureFormat::Bgra8Unorm),
+        "rgba8unorm-srgb" => Ok(wgpu::TextureFormat::Rgba8UnormSrgb),
+        "rgba8unorm" => Ok(wgpu::TextureFormat::Rgba8Unorm),
+        "rgba16float" => Ok(wgpu::TextureFormat::Rgba16Float),
+        "rgb10a2unorm" => Ok(wgpu::TextureFormat::Rgb10a2Unorm),
+        _ => Err(format!(
+            "expected `bgra8unorm-srgb`, `bgra8unorm`, `rgba8unorm-srgb`, `rgba8unorm`, \
+             `rgba16float` or `rgb10a2unorm`, got `{}`",
+            s
+        )),
+    }
+}
+
+/// Parses a comma separated list of backends, such as `vulkan,gl`.
+fn parse_backends(s: &str) -> Result<wgpu::BackendBit, String> {
+    s.split(',')
+        .map(|backend| match backend.trim().to_lowercase().as_str() {
+            "vulkan" => Ok(wgpu::BackendBit::VULKAN),
+            "dx12" => Ok(wgpu::BackendBit::DX12),
+            "dx11" => Ok(wgpu::BackendBit::DX11),
+            "metal" => Ok(wgpu::BackendBit::METAL),
+            "gl" => Ok(wgpu::BackendBit::GL),
+            "webgpu" => Ok(wgpu::BackendBit::BROWSER_WEBGPU),
+            "primary" => Ok(wgpu::BackendBit::PRIMARY),
+            "secondary" => Ok(wgpu::BackendBit::SECONDARY),
+            "all" => Ok(wgpu::BackendBit::all()),
+            _ => Err(format!("unknown backend `{}`", backend)),
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(|backends| {
+            backends
+                .into_iter()
+                .fold(wgpu::BackendBit::empty(), |all, backend| all | backend)
+        })
+}
+
+/// Parses `binding=path`.
+fn parse_buffer_file(s: &str) -> Result<BufferFile, String> {
+    let (binding, path) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected `binding=path`, got `{}`", s))?;
+    let binding = binding
+        .trim()
+        .parse()
+        .map_err(|err| format!("invalid binding `{}`: {}", binding, err))?;
+    Ok(BufferFile {
+        binding,
+        path: path.into(),
+    })
+}
+
+impl Options {
+    fn verbosity(&self) -> Verbosity {
+        match (self.quiet, self.verbose) {
+            (true, _) => Verbosity::Quiet,
+            (false, 0) => Verbosity::Normal,
+            (false, 1) => Verbosity::Verbose,
+            (false, _) => Verbosity::Debug,
+        }
+    }
+}
+
+/// Parses `start..end`.
+fn parse_range(s: &str) -> Result<Range<u32>, String> {
+    let (start, end) = s
+        .split_once("..")
+        .ok_or_else(|| format!("expected `start..end`, got `{}`", s))?;
+    let parse = |bound: &str| {
+        bound
+            .trim()
+            .parse::<u32>()
+            .map_err(|err| format!("invalid bound `{}`: {}", bound, err))
+    };
+    Ok(parse(start)?..parse(end)?)
+}
+
+impl Default for Options {
+    /// The options of a command line giving none, so that the defaults are only spelled out once,
+    /// in the `default_value`s above.
+    fn default() -> Self {
+        Self::parse_from(std::iter::once(env!("CARGO_PKG_NAME")))
+    }
+}
+
+#[derive(Debug)]
+pub enum OptionsError {
+    /// `--image` or `--expect` was given without both `--width` and `--height`.
+    ImageWithoutSize,
+    NoIterations,
+    /// `--max-map-size` is smaller than a single copyable chunk.
+    MaxMapSizeTooSmall,
+    /// `--count-only` was given along with an option that needs the results read back.
+    CountOnlyWithResults,
+}
+
+impl fmt::Display for OptionsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OptionsError::ImageWithoutSize => {
+                f.write_str("--image and --expect require both --width and --height")
+            }
+            OptionsError::NoIterations => f.write_str("--iterations must be at least 1"),
+            OptionsError::MaxMapSizeTooSmall => write!(
+                f,
+                "--max-map-size must be at least {} bytes",
+                wgpu::COPY_BUFFER_ALIGNMENT
+            ),
+            OptionsError::CountOnlyWithResults => f.write_str(
+                "--count-only reads back no results for --output-mmap, --image or --expect",
+            ),
+        }
+    }
+}
+
+impl std::error::Error for OptionsError {}
+
+/// Configures a run of the runner without going through command line parsing.
+///
+/// Starts out with the same defaults as the command line, and checks the combination of options
+/// in [`OptionsBuilder::build`].
+#[derive(Default)]
+pub struct OptionsBuilder {
+    options: Options,
+}
+
+impl OptionsBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn shader(mut self, shader: RustGPUShader) -> Self {
+        self.options.shader = shader;
+        self
+    }
+
+    pub fn verbosity(mut self, verbosity: Verbosity) -> Self {
+        self.options.quiet = verbosity == Verbosity::Quiet;
+        self.options.verbose = match verbosity {
+            Verbosity::Quiet | Verbosity::Normal => 0,
+            Verbosity::Verbose => 1,
+            Verbosity::Debug => 2,
+        };
+        self
+    }
+
+    /// Build every shader crate and report the entry points, capabilities and resource bindings
+    /// of each, instead of running a shader.
+    pub fn report(mut self, report: bool) -> Self {
+        self.options.report = report;
+        self
+    }
+
+    /// Build the shader and print its interface, instead of running it.
+    pub fn describe(mut self, describe: bool) -> Self {
+        self.options.describe = describe;
+        self
+    }
+
+    /// Build the shader, then touch its source and time the rebuild done by watching it, instead
+    /// of running it.
+    pub fn time_rebuild(mut self, time_rebuild: bool) -> Self {
+        self.options.time_rebuild = time_rebuild;
+        self
+    }
+
+    /// Print how long each function of the shader crate took to codegen after each build.
+    pub fn time_entry_points(mut self, time_entry_points: bool) -> Self {
+        self.options.time_entry_points = time_entry_points;
+        self
+    }
+
+    /// Print the `cargo` command that builds the shader crate before each build.
+    pub fn print_build_command(mut self, print_build_command: bool) -> Self {
+        self.options.print_build_command = print_build_command;
+        self
+    }
+
+    /// Build and reflect the compute shader, and print the bindings, dispatch and buffer sizes
+    /// that running it would use, without touching the GPU.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.options.dry_run = dry_run;
+        self
+    }
+
+    /// Build the shader (or load the `spv_file`) and run `spirv-val` over it, instead of running
+    /// it.
+    pub fn validate_only(mut self, validate_only: bool) -> Self {
+        self.options.validate_only = validate_only;
+        self
+    }
+
+    /// Print the compute results (or report) as JSON instead of human readable text.
+    pub fn json(mut self, json: bool) -> Self {
+        self.options.json = json;
+        self
+    }
+
+    /// Print a hash of the compute results instead of the results themselves.
+    pub fn hash(mut self, hash: bool) -> Self {
+        self.options.hash = hash;
+        self
+    }
+
+    /// Keep re-running the compute shader, picking up changes to its source as they're made.
+    pub fn keep_running(mut self, keep_running: bool) -> Self {
+        self.options.keep_running = keep_running;
+        self
+    }
+
+    /// Prompt for new input ranges to re-run the compute shader with, reusing the same device.
+    pub fn repl(mut self, repl: bool) -> Self {
+        self.options.repl = repl;
+        self
+    }
+
+    /// The number of timed dispatches of the compute shader.
+    pub fn iterations(mut self, iterations: u32) -> Self {
+        self.options.iterations = iterations;
+        self
+    }
+
+    /// The number of untimed dispatches before the timed ones.
+    pub fn warmup(mut self, warmup: u32) -> Self {
+        self.options.warmup = Some(warmup);
+        self
+    }
+
+    /// Re-run the compute shader up to `max_runs` times, failing as soon as a run's results
+    /// differ from the first run's.
+    pub fn repeat_until_diff(mut self, max_runs: u32) -> Self {
+        self.options.repeat_until_diff = Some(max_runs);
+        self
+    }
+
+    /// How to interpret the compute results.
+    pub fn output_type(mut self, output_type: OutputType) -> Self {
+        self.options.output_type = output_type;
+        self
+    }
+
+    /// Write the compute results to a PNG at `path`, interpreted as `width` x `height` RGBA8
+    /// pixels.
+    pub fn image(mut self, path: impl Into<PathBuf>, width: u32, height: u32) -> Self {
+        self.options.image = Some(path.into());
+        self.options.width = Some(width);
+        self.options.height = Some(height);
+        self
+    }
+
+    /// Compare the compute results, as an image of the size given to [`OptionsBuilder::image`],
+    /// against the PNG at `path`, allowing each channel to differ by up to `tolerance`.
+    pub fn expect(mut self, path: impl Into<PathBuf>, tolerance: u8) -> Self {
+        self.options.expect = Some(path.into());
+        self.options.tolerance = tolerance;
+        self
+    }
+
+    /// An 8-bit RGBA PNG to bind for compute shaders that sample a texture, or to add to the
+    /// array bound for shaders sampling several.
+    pub fn texture(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.texture.push(path.into());
+        self
+    }
+
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.options.filter = filter;
+        self
+    }
+
+    /// Give up on GPU work that takes longer than this many seconds.
+    pub fn timeout(mut self, seconds: u64) -> Self {
+        self.options.timeout = Some(seconds);
+        self
+    }
+
+    /// Read back compute results larger than `bytes` in chunks of at most `bytes`.
+    pub fn max_map_size(mut self, bytes: u64) -> Self {
+        self.options.max_map_size = Some(bytes);
+        self
+    }
+
+    /// Read the compute results back straight into the memory-mapped file at `path`.
+    pub fn output_mmap(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.output_mmap = Some(path.into());
+        self
+    }
+
+    /// Fail if the compute shader declares more than `bytes` of workgroup shared memory.
+    pub fn max_workgroup_storage(mut self, bytes: u64) -> Self {
+        self.options.max_workgroup_storage = Some(bytes);
+        self
+    }
+
+    /// Read back only the counter the compute shader declares at binding 1.
+    pub fn count_only(mut self, count_only: bool) -> Self {
+        self.options.count_only = count_only;
+        self
+    }
+
+    /// Warn if the dispatch leaves its results just as they were uploaded.
+    pub fn sanity(mut self, sanity: bool) -> Self {
+        self.options.sanity = sanity;
+        self
+    }
+
+    /// Write a timeline of the compute run to `path`, in the Chrome trace event format.
+    pub fn trace(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.trace = Some(path.into());
+        self
+    }
+
+    /// Run compute shaders on the first adapter whose name contains `name`, ignoring case.
+    pub fn adapter(mut self, name: impl Into<String>) -> Self {
+        self.options.adapter = Some(name.into());
+        self
+    }
+
+    /// Run compute shaders on a software adapter such as lavapipe or WARP, which gives the same
+    /// results on every machine.
+    pub fn software(mut self, software: bool) -> Self {
+        self.options.software = software;
+        self
+    }
+
+    /// Hand compute shaders to the driver without validating them with naga, where supported.
+    pub fn passthrough(mut self, passthrough: bool) -> Self {
+        self.options.passthrough = passthrough;
+        self
+    }
+
+    /// Run the compute shader both validated with naga and passed through, comparing the results.
+    pub fn compare_passthrough(mut self, compare_passthrough: bool) -> Self {
+        self.options.compare_passthrough = compare_passthrough;
+        self
+    }
+
+    pub fn power_preference(mut self, power_preference: wgpu::PowerPreference) -> Self {
+        self.options.power_pref = Some(power_preference);
+        self
+    }
+
+    /// Look for adapters on `backends`, rather than the platform's primary backends.
+    pub fn backends(mut self, backends: wgpu::BackendBit) -> Self {
+        self.options.backends = Some(backends);
+        self
+    }
+
+    /// Present graphics shaders with `present_mode` rather than `Mailbox`.
+    pub fn present_mode(mut self, present_mode: wgpu::PresentMode) -> Self {
+        self.options.present_mode = Some(present_mode);
+        self
+    }
+
+    /// Render graphics shaders to a surface of `format`, rather than the sRGB version of the
+    /// surface's preferred format.
+    pub fn surface_format(mut self, format: wgpu::TextureFormat) -> Self {
+        self.options.surface_format = Some(format);
+        self
+    }
+
+    /// Request `features` for the graphics device, on top of those the runner needs itself.
+    pub fn features(mut self, features: wgpu::Features) -> Self {
+        self.options.features.push(features);
+        self
+    }
+
+    /// Dispatch a 2D or 3D grid of `[x, y, z]` invocations instead of one invocation per element
+    /// of the input.
+    pub fn dispatch_dims(mut self, dims: [u32; 3]) -> Self {
+        self.options.dispatch_dims = Some(dims);
+        self
+    }
+
+    /// Fill the elements in `range` (or all of them) of the storage buffer with `value` before
+    /// every dispatch, after the input is copied in.
+    pub fn clear(mut self, value: u32, range: Option<Range<u32>>) -> Self {
+        self.options.clear = Some(value);
+        self.options.clear_range = range;
+        self
+    }
+
+    /// Bind the raw contents of the file at `path` as the storage buffer at `binding`.
+    pub fn input(mut self, binding: u32, path: impl Into<PathBuf>) -> Self {
+        self.options.input.push(BufferFile {
+            binding,
+            path: path.into(),
+        });
+        self
+    }
+
+    /// Write the raw contents of the storage buffer at `binding` to `path` after the run.
+    pub fn output(mut self, binding: u32, path: impl Into<PathBuf>) -> Self {
+        self.options.output.push(BufferFile {
+            binding,
+            path: path.into(),
+        });
+        self
+    }
+
+    /// Bind an append buffer at `binding`, with a zeroed `u32` cursor followed by room for
+    /// `capacity` elements. Only the elements the cursor counts are read back.
+    pub fn append(mut self, binding: u32, capacity: u32) -> Self {
+        self.options.append.push(AppendBuffer { binding, capacity });
+        self
+    }
+
+    /// The byte order of the `u32`s in `input` files, and to write `output` files in.
+    pub fn input_endian(mut self, endian: Endian) -> Self {
+        self.options.input_endian = endian;
+        self
+    }
+
+    /// Enables a cargo feature of the shader crate.
+    pub fn shader_feature(mut self, feature: impl Into<String>) -> Self {
+        self.options.shader_features.push(feature.into());
+        self
+    }
+
+    /// Run this precompiled SPIR-V compute shader instead of building the shader.
+    pub fn spv_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.options.spv_file = Some(path.into());
+        self
+    }
+
+    /// Print the record of the invocation at the flattened global invocation id `index` from the
+    /// shader's debug buffer, see [`DEBUG_BINDING`].
+    pub fn debug_index(mut self, index: u64) -> Self {
+        self.options.debug_index.push(index);
+        self
+    }
+
+    /// The name of the compute entry point to run, `main_cs` by default.
+    pub fn entry_point(mut self, name: impl Into<String>) -> Self {
+        self.options.entry_point = name.into();
+        self
+    }
+
+    /// Calls `on_rebuild` with the result of every successful shader build, e.g. to let an editor
+    /// know a new binary is being run.
+    #[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+    pub fn on_rebuild(
+        mut self,
+        on_rebuild: impl Fn(&spirv_builder::CompileResult) + Send + Sync + 'static,
+    ) -> Self {
+        self.options.on_rebuild = Some(std::sync::Arc::new(on_rebuild));
+        self
+    }
+
+    pub fn build(self) -> Result<Options, OptionsError> {
+        let options = self.options;
+        if (options.image.is_some() || options.expect.is_some())
+            && (options.width.is_none() || options.height.is_none())
+        {
+            return Err(OptionsError::ImageWithoutSize);
+        }
+        if options.iterations == 0 {
+            return Err(OptionsError::NoIterations);
+        }
+        if matches!(options.max_map_size, Some(max) if max < wgpu::COPY_BUFFER_ALIGNMENT) {
+            return Err(OptionsError::MaxMapSizeTooSmall);
+        }
+        if options.count_only
+            && (options.output_mmap.is_some()
+                || options.image.is_some()
+                || options.expect.is_some())
+        {
+            return Err(OptionsError::CountOnlyWithResults);
+        }
+        Ok(options)
+    }
+}
+
+impl From<Options> for OptionsBuilder {
+    fn from(options: Options) -> Self {
+        Self { options }
+    }
 }
 
 #[cfg_attr(target_os = "android", ndk_glue::main(backtrace = "on"))]
 pub fn main() {
-    let options: Options = Options::parse();
+    let options = match OptionsBuilder::from(Options::parse()).build() {
+        Ok(options) => options,
+        Err(err) => panic!("{}", err),
+    };
+    run(&options);
+}
+
+/// Builds every example shader and runs it once, printing whether each of them passed, for the
+/// `smoke` binary. Returns whether none of them failed; shaders that can't be run for lack of a
+/// GPU are only built and reflected, and don't count as failures.
+#[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+pub fn smoke() -> bool {
+    smoke::run()
+}
+
+/// Runs the `spv-diff` binary, which prints how two SPIR-V modules differ, returning its exit
+/// code. See [`diff_spirv`].
+#[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+pub fn spv_diff() -> i32 {
+    spv_diff::run()
+}
 
-    if is_compute_shader(options.shader) {
-        compute::start(&options)
+/// Runs the runner as configured by `options`, e.g. from an [`OptionsBuilder`].
+pub fn run(options: &Options) {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        // An explicit `RUST_LOG` takes precedence over `--quiet` and `-v`.
+        if std::env::var_os("RUST_LOG").is_none() {
+            let mut filter = options.verbosity().log_filter().to_owned();
+            // The validation layer reports `debug_printf!` output as info messages, which wgpu
+            // logs like any other.
+            if cfg!(feature = "debug-printf") {
+                filter.push_str(",gfx_backend_vulkan=info");
+            }
+            std::env::set_var("RUST_LOG", filter);
+        }
+        if cfg!(feature = "debug-printf") && std::env::var_os("VK_LAYER_ENABLES").is_none() {
+            std::env::set_var(
+                "VK_LAYER_ENABLES",
+                "VK_VALIDATION_FEATURE_ENABLE_DEBUG_PRINTF_EXT",
+            );
+        }
+        wgpu_subscriber::initialize_default_subscriber(None);
+    }
+    #[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+    if options.report {
+        return report::print(options);
+    }
+    #[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+    if options.describe {
+        if !report::describe(options) {
+            panic!("The shader couldn't be described");
+        }
+        return;
+    }
+    #[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+    if options.time_rebuild {
+        return rebuild::time(options);
+    }
+    #[cfg(not(any(target_os = "android", target_arch = "wasm32")))]
+    if options.validate_only {
+        if !validate::run(options) {
+            panic!("The shader failed validation");
+        }
+        return;
+    }
+    if options.spv_file.is_some() || is_compute_shader(options.shader) {
+        compute::start(options)
     } else {
-        graphics::start(&options);
+        graphics::start(options);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn default_options() {
+        let options = Options::default();
+        assert!(options.shader == RustGPUShader::Sky);
+        assert_eq!(options.iterations, 1);
+        assert!(options.output_type == OutputType::U32);
+        assert_eq!(options.tolerance, 2);
+        assert!(options.input_endian == Endian::Le);
+        assert_eq!(options.entry_point, "main_cs");
+    }
+
+    #[test]
+    fn parse_ranges() {
+        assert_eq!(parse_range("0..16"), Ok(0..16));
+        assert_eq!(parse_range(" 4 .. 8 "), Ok(4..8));
+        assert_eq!(parse_range("8..4"), Ok(8..4));
+        assert!(parse_range("16").is_err());
+        assert!(parse_range("0..").is_err());
+        assert!(parse_range("-1..4").is_err());
+        assert!(parse_range("0..=4").is_err());
+    }
+
+    #[test]
+    fn parse_buffer_files() {
+        let file = parse_buffer_file("2=input.bin").unwrap();
+        assert_eq!(file.binding, 2);
+        assert_eq!(file.path, Path::new("input.bin"));
+        // Only the first `=` separates the binding, the path may contain more.
+        let file = parse_buffer_file(" 3 =a=b.bin").unwrap();
+        assert_eq!(file.binding, 3);
+        assert_eq!(file.path, Path::new("a=b.bin"));
+        assert!(parse_buffer_file("input.bin").is_err());
+        assert!(parse_buffer_file("x=input.bin").is_err());
+    }
+
+    #[test]
+    fn parse_backend_lists() {
+        assert_eq!(parse_backends("vulkan"), Ok(wgpu::BackendBit::VULKAN));
+        assert_eq!(
+            parse_backends("Vulkan, gl"),
+            Ok(wgpu::BackendBit::VULKAN | wgpu::BackendBit::GL)
+        );
+        assert_eq!(parse_backends("all"), Ok(wgpu::BackendBit::all()));
+        assert_eq!(
+            parse_backends("vulkan,opengl"),
+            Err("unknown backend `opengl`".to_string())
+        );
+        assert!(parse_backends("").is_err());
+    }
+
+    #[test]
+    fn parse_features() {
+        assert_eq!(
+            parse_feature("push-constants"),
+            Ok(wgpu::Features::PUSH_CONSTANTS)
+        );
+        assert_eq!(
+            parse_feature("Shader_Float64"),
+            Ok(wgpu::Features::SHADER_FLOAT64)
+        );
+        let err = parse_feature("push-constant").unwrap_err();
+        assert!(err.contains("push_constants"), "{}", err);
+        assert!(err.ends_with("got `push-constant`"), "{}", err);
     }
 }
@@ -0,0 +1,97 @@
+//! `--output-mmap`: compute results read back straight into a memory-mapped file, for results too
+//! large to hold in memory as well as in the GPU's readback buffer.
+
+use crate::RunnerError;
+use memmap2::MmapMut;
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+
+/// How much of the mapped GPU buffer is copied into the file at a time, so the kernel can write
+/// back the pages already copied while the rest are being copied.
+const COPY_CHUNK: usize = 64 << 20;
+
+/// A file sized to hold the results, mapped into memory to be written in place.
+pub struct OutputFile {
+    path: PathBuf,
+    map: MmapMut,
+}
+
+impl OutputFile {
+    /// Creates (or truncates) the file at `path` and sizes it to `len` bytes, after checking its
+    /// file system has room for them, so that running out of space fails here rather than as a
+    /// `SIGBUS` when a page of the mapping is written.
+    pub fn create(path: &Path, len: usize) -> Result<Self, RunnerError> {
+        let error =
+            |err: std::io::Error| RunnerError::OutputFile(format!("{}: {}", path.display(), err));
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        if let Some(available) = available_space(dir) {
+            if available < len as u64 {
+                return Err(RunnerError::OutputFile(format!(
+                    "{}: the results take {} bytes, but only {} are available",
+                    path.display(),
+                    len,
+                    available
+                )));
+            }
+        }
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+            .map_err(error)?;
+        file.set_len(len as u64).map_err(error)?;
+        // Safety: the file was just created for the runner alone. Another process truncating it
+        // meanwhile would make writing to the mapping fault, as for any other mapped file.
+        let map = unsafe { MmapMut::map_mut(&file) }.map_err(error)?;
+        Ok(Self {
+            path: path.to_owned(),
+            map,
+        })
+    }
+
+    /// The whole file, to be written in place.
+    pub fn bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.map
+    }
+
+    /// Copies `data` to the start of the file, [`COPY_CHUNK`] bytes at a time.
+    pub fn copy_from(&mut self, data: &[u8]) {
+        for (dst, src) in self.map.chunks_mut(COPY_CHUNK).zip(data.chunks(COPY_CHUNK)) {
+            dst.copy_from_slice(src);
+        }
+    }
+
+    /// Writes the mapped pages out to the file, reporting any error doing so.
+    pub fn finish(self) -> Result<(), RunnerError> {
+        self.map
+            .flush()
+            .map_err(|err| RunnerError::OutputFile(format!("{}: {}", self.path.display(), err)))
+    }
+}
+
+/// The bytes available to unprivileged users on the file system of `dir`, if that can be found
+/// out. Only Unix-likes are checked.
+#[cfg(unix)]
+fn available_space(dir: &Path) -> Option<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    let dir = CString::new(dir.as_os_str().as_bytes()).ok()?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // Safety: `dir` is a valid C string, and `stat` is only read if `statvfs` filled it in.
+    if unsafe { libc::statvfs(dir.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    let stat = unsafe { stat.assume_init() };
+    #[allow(clippy::useless_conversion)] // The field types differ between platforms.
+    Some(u64::from(stat.f_bavail) * u64::from(stat.f_frsize))
+}
+
+#[cfg(not(unix))]
+fn available_space(_dir: &Path) -> Option<u64> {
+    None
+}
@@ -0,0 +1,74 @@
+//! `--time-rebuild`: times a full build of the shader crate against an incremental rebuild of it.
+
+use crate::{shader_builder, shader_crate_path, Options};
+use serde::Serialize;
+use std::sync::mpsc::sync_channel;
+use std::time::Instant;
+
+#[derive(Serialize)]
+struct RebuildTimes {
+    shader: String,
+    initial_build_secs: f64,
+    rebuild_secs: f64,
+}
+
+/// Builds `--shader`, then starts watching it, touches its `src/lib.rs` and waits for the rebuild
+/// the watcher does in response, printing how long both builds took.
+///
+/// The initial build is only as cold as the target directory: run `cargo clean` first to include
+/// building the dependencies. The rebuild includes the time `notify` takes to report the change,
+/// which is small in comparison.
+pub fn time(options: &Options) {
+    let start = Instant::now();
+    if shader_builder(options, options.shader).build().is_err() {
+        // The compiler has already said why.
+        return;
+    }
+    let initial_build = start.elapsed();
+
+    // Watching starts with a build of its own, which is a no-op after the one above.
+    let (tx, rx) = sync_channel(1);
+    let builder = shader_builder(options, options.shader);
+    let thread = std::thread::spawn(move || {
+        let result = builder.watch(move |_| {
+            let _ = tx.send(Instant::now());
+        });
+        if let Err(err) = result {
+            eprintln!("Failed to watch the shader: {}", err);
+        }
+    });
+    std::mem::forget(thread);
+    if rx.recv().is_err() {
+        return;
+    }
+
+    let source = shader_crate_path(options.shader).join("src").join("lib.rs");
+    let touched = Instant::now();
+    // Rewriting the file as it is updates its modification time, without any extra dependencies.
+    if let Err(err) = std::fs::read(&source).and_then(|contents| std::fs::write(&source, contents))
+    {
+        eprintln!("Failed to touch {}: {}", source.display(), err);
+        return;
+    }
+    let rebuild = match rx.recv() {
+        Ok(finished) => finished - touched,
+        // The watcher failed, and said why.
+        Err(_) => return,
+    };
+
+    if options.json {
+        let times = RebuildTimes {
+            shader: options.shader.to_string(),
+            initial_build_secs: initial_build.as_secs_f64(),
+            rebuild_secs: rebuild.as_secs_f64(),
+        };
+        println!("{}", serde_json::to_string_pretty(&times).unwrap());
+    } else {
+        println!("Initial build: {:?}", initial_build);
+        println!("Rebuild after touching {}: {:?}", source.display(), rebuild);
+        match initial_build.checked_sub(rebuild) {
+            Some(saved) => println!("The rebuild was {:?} faster", saved),
+            None => println!("The rebuild was {:?} slower", rebuild - initial_build),
+        }
+    }
+}
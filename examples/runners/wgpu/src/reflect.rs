@@ -0,0 +1,694 @@
+//! Just enough SPIR-V reflection to build pipeline layouts from a shader module, rather than
+//! hardcoding them on the host side.
+
+use rspirv::binary::{Consumer, ParseAction, ParseState};
+use rspirv::dr::{Instruction, Loader, Module, ModuleHeader, Operand};
+use rspirv::spirv::{
+    BuiltIn, Capability, Decoration, ExecutionMode, ExecutionModel, Op, StorageClass, Word,
+};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// What kind of resource a [`Binding`] expects.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BindingKind {
+    Buffer,
+    Sampler,
+    /// An image that's read through a sampler.
+    Texture,
+    /// An image that's read or written directly.
+    StorageTexture,
+}
+
+/// How a shader accesses a buffer [`Binding`], which the layout it's bound with has to agree on.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BufferAccess {
+    /// A uniform buffer, in the `Uniform` storage class.
+    Uniform,
+    /// A storage buffer whose every member is decorated `NonWritable`, e.g. a `&[u32]`.
+    ReadOnly,
+    ReadWrite,
+}
+
+/// The length of a binding array, e.g. `&[Image; 4]` or `&RuntimeArray<Image>`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ArrayLength {
+    Fixed(u32),
+    Runtime,
+}
+
+/// A resource binding declared by a shader module.
+#[derive(Debug)]
+pub struct Binding {
+    pub set: u32,
+    pub binding: u32,
+    pub storage_class: StorageClass,
+    /// The kind of each element, if this is a binding array.
+    pub kind: BindingKind,
+    /// How the shader accesses the buffer, for buffer bindings.
+    pub access: Option<BufferAccess>,
+    pub array: Option<ArrayLength>,
+    /// Whether the bound type ends in a runtime-sized array (e.g. a `&mut [u32]` parameter).
+    pub runtime_array: bool,
+    /// The size in bytes of the bound type, not counting a trailing runtime-sized array.
+    pub size: Option<u64>,
+    /// The size in bytes of each element of the trailing runtime-sized array, if there is one.
+    pub element_size: Option<u64>,
+    /// The members of the bound type, if it's a struct.
+    pub members: Vec<Member>,
+}
+
+/// A member of a struct bound by a shader.
+#[derive(Debug)]
+pub struct Member {
+    /// The name of the member, if the module still has its debug names.
+    pub name: Option<String>,
+    pub offset: Option<u32>,
+}
+
+/// An entry point declared by a shader module.
+#[derive(Debug)]
+pub struct EntryPoint {
+    pub name: String,
+    pub execution_model: ExecutionModel,
+    /// The workgroup size of a compute entry point, from its `LocalSize` execution mode.
+    pub local_size: Option<[u32; 3]>,
+    /// The inputs and outputs of the entry point that have a `Location`, e.g. vertex attributes.
+    /// Built-ins such as `position` aren't included.
+    pub interface: Vec<InterfaceVariable>,
+    /// Where the entry point's function starts in the Rust source, see
+    /// [`Reflection::function_sources`].
+    pub source: Option<SourceLocation>,
+}
+
+/// A place in the Rust source a shader module was built from, as recorded by the `OpLine`
+/// debug info rust-gpu emits.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceLocation {
+    /// The file, as named by the module's `OpString`, usually an absolute path.
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+/// An input or output of an entry point at a `Location`.
+#[derive(Debug)]
+pub struct InterfaceVariable {
+    pub location: u32,
+    /// Either `Input` or `Output`.
+    pub storage_class: StorageClass,
+    /// The component type and count, if the variable is a 32-bit scalar or vector.
+    pub format: Option<(ComponentType, u32)>,
+    pub interpolation: Interpolation,
+}
+
+/// The type of each component of an [`InterfaceVariable`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ComponentType {
+    Float,
+    Uint,
+    Sint,
+}
+
+/// How an [`InterfaceVariable`] is interpolated, e.g. `Flat` for `#[spirv(flat)]`. Only
+/// meaningful for fragment inputs and the vertex outputs feeding them.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Interpolation {
+    Smooth,
+    Flat,
+    NoPerspective,
+}
+
+#[derive(Debug)]
+pub struct Reflection {
+    pub capabilities: Vec<Capability>,
+    /// The built-ins any variable of the module is decorated with, e.g. `ViewIndex`.
+    pub built_ins: Vec<BuiltIn>,
+    pub entry_points: Vec<EntryPoint>,
+    pub bindings: Vec<Binding>,
+    /// The bytes of workgroup shared memory declared by the module's `Workgroup` variables, e.g.
+    /// `#[spirv(workgroup)] shared: &mut [u32; 64]`, counted without padding between members.
+    /// Modules don't say which entry point uses which before SPIR-V 1.4, so this is the total
+    /// over the whole module.
+    pub workgroup_storage: u64,
+    /// The size in bytes of the module's push constant block, e.g. a
+    /// `#[spirv(push_constant)] constants: &Constants` parameter, if it declares one.
+    pub push_constant_size: Option<u64>,
+    /// Where each function with a debug name starts in the Rust source, from the first `OpLine`
+    /// in its body. Empty if the module was built without line info or had it stripped.
+    pub function_sources: Vec<(String, SourceLocation)>,
+}
+
+impl Reflection {
+    pub fn new(spirv: &[u32]) -> Result<Self, ParseState> {
+        let mut loader = Loader::new();
+        rspirv::binary::parse_words(spirv, &mut loader)?;
+        let module = loader.module();
+        let types = Types::new(&module);
+
+        let capabilities = module
+            .capabilities
+            .iter()
+            .filter_map(|inst| match inst.operands.get(0)? {
+                Operand::Capability(capability) => Some(*capability),
+                _ => None,
+            })
+            .collect();
+        let built_ins = module
+            .annotations
+            .iter()
+            .filter(|inst| inst.class.opcode == Op::Decorate)
+            .filter_map(|inst| match inst.operands.get(2)? {
+                Operand::BuiltIn(built_in) => Some(*built_in),
+                _ => None,
+            })
+            .collect();
+        let local_sizes = module
+            .execution_modes
+            .iter()
+            .filter(|inst| {
+                inst.operands.get(1) == Some(&Operand::ExecutionMode(ExecutionMode::LocalSize))
+            })
+            .filter_map(|inst| {
+                let size = [
+                    Types::literal_operand(inst, 2)?,
+                    Types::literal_operand(inst, 3)?,
+                    Types::literal_operand(inst, 4)?,
+                ];
+                Some((Types::id_operand(inst, 0)?, size))
+            })
+            .collect::<HashMap<_, _>>();
+        let function_starts = function_starts(&module);
+        let entry_points = module
+            .entry_points
+            .iter()
+            .filter_map(|inst| {
+                let execution_model = match inst.operands.get(0)? {
+                    Operand::ExecutionModel(execution_model) => *execution_model,
+                    _ => return None,
+                };
+                let name = match inst.operands.get(2)? {
+                    Operand::LiteralString(name) => name.clone(),
+                    _ => return None,
+                };
+                let local_size = local_sizes.get(&Types::id_operand(inst, 1)?).copied();
+                let interface = (3..inst.operands.len())
+                    .filter_map(|index| types.interface_variable(Types::id_operand(inst, index)?))
+                    .collect();
+                let source = function_starts.get(&Types::id_operand(inst, 1)?).cloned();
+                Some(EntryPoint {
+                    name,
+                    execution_model,
+                    local_size,
+                    interface,
+                    source,
+                })
+            })
+            .collect();
+
+        let bindings = module
+            .types_global_values
+            .iter()
+            .filter(|inst| inst.class.opcode == Op::Variable)
+            .filter_map(|var| {
+                let id = var.result_id?;
+                let set = types.decoration(id, Decoration::DescriptorSet)?;
+                let binding = types.decoration(id, Decoration::Binding)?;
+                let storage_class = match var.operands[0] {
+                    Operand::StorageClass(storage_class) => storage_class,
+                    _ => return None,
+                };
+                let pointee = types.pointee(var.result_type?)?;
+                let (element, array) = types.binding_array(pointee);
+                let kind = types.binding_kind(element);
+                let access = if kind == BindingKind::Buffer {
+                    types.buffer_access(id, storage_class, element)
+                } else {
+                    None
+                };
+                let (runtime_array, size) = match types.runtime_array_offset(pointee) {
+                    Some(offset) => (true, Some(offset)),
+                    None => (false, types.size_of(pointee)),
+                };
+                let element_size = types.runtime_array_stride(pointee);
+                let members = types.members(pointee);
+                Some(Binding {
+                    set,
+                    binding,
+                    storage_class,
+                    kind,
+                    access,
+                    array,
+                    runtime_array,
+                    size,
+                    element_size,
+                    members,
+                })
+            })
+            .collect();
+
+        let workgroup_storage = module
+            .types_global_values
+            .iter()
+            .filter(|inst| inst.class.opcode == Op::Variable)
+            .filter(|var| {
+                var.operands.get(0) == Some(&Operand::StorageClass(StorageClass::Workgroup))
+            })
+            .filter_map(|var| types.size_of(types.pointee(var.result_type?)?))
+            .sum();
+        let push_constant_size = module
+            .types_global_values
+            .iter()
+            .filter(|inst| inst.class.opcode == Op::Variable)
+            .filter(|var| {
+                var.operands.get(0) == Some(&Operand::StorageClass(StorageClass::PushConstant))
+            })
+            .filter_map(|var| types.size_of(types.pointee(var.result_type?)?))
+            .max();
+        let function_sources = module
+            .debug_names
+            .iter()
+            .filter(|inst| inst.class.opcode == Op::Name)
+            .filter_map(|inst| {
+                let source = function_starts.get(&Types::id_operand(inst, 0)?)?;
+                match inst.operands.get(1)? {
+                    Operand::LiteralString(name) => Some((name.clone(), source.clone())),
+                    _ => None,
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            capabilities,
+            built_ins,
+            entry_points,
+            bindings,
+            workgroup_storage,
+            push_constant_size,
+            function_sources,
+        })
+    }
+
+    pub fn entry_point(&self, name: &str, execution_model: ExecutionModel) -> Option<&EntryPoint> {
+        self.entry_points
+            .iter()
+            .find(|e| e.name == name && e.execution_model == execution_model)
+    }
+
+    /// The first binding in `set` of the given kind.
+    pub fn binding_of_kind(&self, set: u32, kind: BindingKind) -> Option<&Binding> {
+        self.bindings
+            .iter()
+            .find(|b| b.set == set && b.kind == kind)
+    }
+
+    pub fn binding(&self, set: u32, binding: u32) -> Option<&Binding> {
+        self.bindings
+            .iter()
+            .find(|b| b.set == set && b.binding == binding)
+    }
+}
+
+/// The location of the first `OpLine` in the body of each function of `module`, by the function's
+/// id.
+fn function_starts(module: &Module) -> HashMap<Word, SourceLocation> {
+    let files = module
+        .debug_string_source
+        .iter()
+        .filter(|inst| inst.class.opcode == Op::String)
+        .filter_map(|inst| match inst.operands.get(0)? {
+            Operand::LiteralString(file) => Some((inst.result_id?, file.as_str())),
+            _ => None,
+        })
+        .collect::<HashMap<_, _>>();
+    module
+        .functions
+        .iter()
+        .filter_map(|function| {
+            let id = function.def.as_ref()?.result_id?;
+            let line = function
+                .blocks
+                .iter()
+                .flat_map(|block| &block.instructions)
+                .find(|inst| inst.class.opcode == Op::Line)?;
+            let source = SourceLocation {
+                file: files.get(&Types::id_operand(line, 0)?)?.to_string(),
+                line: Types::literal_operand(line, 1)?,
+                column: Types::literal_operand(line, 2)?,
+            };
+            Some((id, source))
+        })
+        .collect()
+}
+
+/// The capabilities `spirv` declares with `OpCapability`, i.e. those the module actually uses.
+///
+/// This only parses as far as the capabilities, which come first in a module, so it's cheap
+/// enough to check a module against a target or adapter before doing anything else with it.
+pub fn used_capabilities(spirv: &[u32]) -> Result<Vec<Capability>, ParseState> {
+    let mut capabilities = Capabilities(Vec::new());
+    match rspirv::binary::parse_words(spirv, &mut capabilities) {
+        Ok(()) | Err(ParseState::ConsumerStopRequested) => Ok(capabilities.0),
+        Err(err) => Err(err),
+    }
+}
+
+/// Collects `OpCapability` instructions, stopping at the first other instruction.
+struct Capabilities(Vec<Capability>);
+
+impl Consumer for Capabilities {
+    fn initialize(&mut self) -> ParseAction {
+        ParseAction::Continue
+    }
+
+    fn finalize(&mut self) -> ParseAction {
+        ParseAction::Continue
+    }
+
+    fn consume_header(&mut self, _: ModuleHeader) -> ParseAction {
+        ParseAction::Continue
+    }
+
+    fn consume_instruction(&mut self, inst: Instruction) -> ParseAction {
+        if inst.class.opcode != Op::Capability {
+            return ParseAction::Stop;
+        }
+        if let Some(Operand::Capability(capability)) = inst.operands.get(0) {
+            self.0.push(*capability);
+        }
+        ParseAction::Continue
+    }
+}
+
+/// Lookup tables over the types, constants and decorations of a module.
+struct Types<'a> {
+    defs: HashMap<Word, &'a Instruction>,
+    // Keyed by `Decoration as u32`, as the `spirv` enums don't implement `Hash`.
+    decorations: HashMap<(Word, u32), u32>,
+    member_offsets: HashMap<(Word, u32), u32>,
+    /// The struct members decorated `NonWritable`.
+    non_writable_members: HashSet<(Word, u32)>,
+    member_names: HashMap<(Word, u32), &'a str>,
+}
+
+impl<'a> Types<'a> {
+    fn new(module: &'a Module) -> Self {
+        let defs = module
+            .types_global_values
+            .iter()
+            .filter_map(|inst| Some((inst.result_id?, inst)))
+            .collect();
+        let mut decorations = HashMap::new();
+        let mut member_offsets = HashMap::new();
+        let mut non_writable_members = HashSet::new();
+        for inst in &module.annotations {
+            let target = match Self::id_operand(inst, 0) {
+                Some(target) => target,
+                None => continue,
+            };
+            match inst.class.opcode {
+                Op::Decorate => {
+                    if let Some(Operand::Decoration(decoration)) = inst.operands.get(1) {
+                        let value = Self::literal_operand(inst, 2).unwrap_or(0);
+                        decorations.insert((target, *decoration as u32), value);
+                    }
+                }
+                Op::MemberDecorate => match inst.operands.get(2) {
+                    Some(Operand::Decoration(Decoration::Offset)) => {
+                        if let (Some(member), Some(offset)) = (
+                            Self::literal_operand(inst, 1),
+                            Self::literal_operand(inst, 3),
+                        ) {
+                            member_offsets.insert((target, member), offset);
+                        }
+                    }
+                    Some(Operand::Decoration(Decoration::NonWritable)) => {
+                        if let Some(member) = Self::literal_operand(inst, 1) {
+                            non_writable_members.insert((target, member));
+                        }
+                    }
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+        let member_names = module
+            .debug_names
+            .iter()
+            .filter(|inst| inst.class.opcode == Op::MemberName)
+            .filter_map(|inst| {
+                let name = match inst.operands.get(2)? {
+                    Operand::LiteralString(name) => name.as_str(),
+                    _ => return None,
+                };
+                Some((
+                    (Self::id_operand(inst, 0)?, Self::literal_operand(inst, 1)?),
+                    name,
+                ))
+            })
+            .collect();
+        Self {
+            defs,
+            decorations,
+            member_offsets,
+            non_writable_members,
+            member_names,
+        }
+    }
+
+    fn decoration(&self, id: Word, decoration: Decoration) -> Option<u32> {
+        self.decorations.get(&(id, decoration as u32)).copied()
+    }
+
+    fn def(&self, id: Word) -> Option<&'a Instruction> {
+        self.defs.get(&id).copied()
+    }
+
+    fn id_operand(inst: &Instruction, index: usize) -> Option<Word> {
+        match inst.operands.get(index)? {
+            Operand::IdRef(id) => Some(*id),
+            _ => None,
+        }
+    }
+
+    fn literal_operand(inst: &Instruction, index: usize) -> Option<u32> {
+        match inst.operands.get(index)? {
+            Operand::LiteralInt32(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    fn pointee(&self, pointer: Word) -> Option<Word> {
+        let inst = self.def(pointer)?;
+        if inst.class.opcode != Op::TypePointer {
+            return None;
+        }
+        Self::id_operand(inst, 1)
+    }
+
+    /// `var` as an [`InterfaceVariable`], if it's an input or output with a `Location`.
+    fn interface_variable(&self, var: Word) -> Option<InterfaceVariable> {
+        let inst = self.def(var)?;
+        let storage_class = match inst.operands.get(0)? {
+            Operand::StorageClass(storage_class)
+                if matches!(storage_class, StorageClass::Input | StorageClass::Output) =>
+            {
+                *storage_class
+            }
+            _ => return None,
+        };
+        let location = self.decoration(var, Decoration::Location)?;
+        let format = self.component_format(self.pointee(inst.result_type?)?);
+        let interpolation = if self.decoration(var, Decoration::Flat).is_some() {
+            Interpolation::Flat
+        } else if self.decoration(var, Decoration::NoPerspective).is_some() {
+            Interpolation::NoPerspective
+        } else {
+            Interpolation::Smooth
+        };
+        Some(InterfaceVariable {
+            location,
+            storage_class,
+            format,
+            interpolation,
+        })
+    }
+
+    /// The component type and count of `ty`, if it's a 32-bit scalar or vector.
+    fn component_format(&self, ty: Word) -> Option<(ComponentType, u32)> {
+        let inst = self.def(ty)?;
+        match inst.class.opcode {
+            Op::TypeFloat if Self::literal_operand(inst, 0)? == 32 => {
+                Some((ComponentType::Float, 1))
+            }
+            Op::TypeInt if Self::literal_operand(inst, 0)? == 32 => {
+                match Self::literal_operand(inst, 1)? {
+                    0 => Some((ComponentType::Uint, 1)),
+                    _ => Some((ComponentType::Sint, 1)),
+                }
+            }
+            Op::TypeVector => {
+                let (component, _) = self.component_format(Self::id_operand(inst, 0)?)?;
+                Some((component, Self::literal_operand(inst, 1)?))
+            }
+            _ => None,
+        }
+    }
+
+    /// The element type and length of `ty` if it's an array of resources, or `ty` itself if not.
+    fn binding_array(&self, ty: Word) -> (Word, Option<ArrayLength>) {
+        let inst = match self.def(ty) {
+            Some(inst) => inst,
+            None => return (ty, None),
+        };
+        let element = match Self::id_operand(inst, 0) {
+            Some(element) => element,
+            None => return (ty, None),
+        };
+        match inst.class.opcode {
+            Op::TypeRuntimeArray => (element, Some(ArrayLength::Runtime)),
+            Op::TypeArray => {
+                let length = Self::id_operand(inst, 1)
+                    .and_then(|length| self.def(length))
+                    .and_then(|length| Self::literal_operand(length, 0));
+                match length {
+                    Some(length) => (element, Some(ArrayLength::Fixed(length))),
+                    None => (ty, None),
+                }
+            }
+            _ => (ty, None),
+        }
+    }
+
+    fn binding_kind(&self, ty: Word) -> BindingKind {
+        let inst = match self.def(ty) {
+            Some(inst) => inst,
+            None => return BindingKind::Buffer,
+        };
+        match inst.class.opcode {
+            Op::TypeSampler => BindingKind::Sampler,
+            // The `Sampled` operand is 2 for images used without a sampler.
+            Op::TypeImage if Self::literal_operand(inst, 5) == Some(2) => {
+                BindingKind::StorageTexture
+            }
+            Op::TypeImage => BindingKind::Texture,
+            _ => BindingKind::Buffer,
+        }
+    }
+
+    /// How the buffer variable `var` of type `ty` is accessed. Storage buffers are read-only when
+    /// either the variable or every member of its struct is decorated `NonWritable`. Uniform
+    /// buffers decorated `BufferBlock` are storage buffers from before the `StorageBuffer`
+    /// storage class.
+    fn buffer_access(
+        &self,
+        var: Word,
+        storage_class: StorageClass,
+        ty: Word,
+    ) -> Option<BufferAccess> {
+        let buffer_block = self.decoration(ty, Decoration::BufferBlock).is_some();
+        match storage_class {
+            StorageClass::Uniform if !buffer_block => return Some(BufferAccess::Uniform),
+            StorageClass::Uniform | StorageClass::StorageBuffer => {}
+            _ => return None,
+        }
+        let member_count = match self.def(ty) {
+            Some(inst) if inst.class.opcode == Op::TypeStruct => inst.operands.len() as u32,
+            _ => 0,
+        };
+        let members_non_writable = member_count > 0
+            && (0..member_count).all(|member| self.non_writable_members.contains(&(ty, member)));
+        if self.decoration(var, Decoration::NonWritable).is_some() || members_non_writable {
+            Some(BufferAccess::ReadOnly)
+        } else {
+            Some(BufferAccess::ReadWrite)
+        }
+    }
+
+    /// The members of `ty`, or nothing if it isn't a struct.
+    fn members(&self, ty: Word) -> Vec<Member> {
+        let count = match self.def(ty) {
+            Some(inst) if inst.class.opcode == Op::TypeStruct => inst.operands.len() as u32,
+            _ => 0,
+        };
+        (0..count)
+            .map(|member| Member {
+                name: self
+                    .member_names
+                    .get(&(ty, member))
+                    .map(|name| name.to_string()),
+                offset: self.member_offsets.get(&(ty, member)).copied(),
+            })
+            .collect()
+    }
+
+    /// The offset of the trailing runtime-sized array in `ty`, if it has one.
+    fn runtime_array_offset(&self, ty: Word) -> Option<u64> {
+        let inst = self.def(ty)?;
+        match inst.class.opcode {
+            Op::TypeRuntimeArray => Some(0),
+            Op::TypeStruct => {
+                let last = inst.operands.len().checked_sub(1)?;
+                let member_ty = Self::id_operand(inst, last)?;
+                let offset = u64::from(*self.member_offsets.get(&(ty, last as u32))?);
+                Some(offset + self.runtime_array_offset(member_ty)?)
+            }
+            _ => None,
+        }
+    }
+
+    /// The stride of the trailing runtime-sized array in `ty`, if it has one.
+    fn runtime_array_stride(&self, ty: Word) -> Option<u64> {
+        let inst = self.def(ty)?;
+        match inst.class.opcode {
+            Op::TypeRuntimeArray => match self.decoration(ty, Decoration::ArrayStride) {
+                Some(stride) => Some(u64::from(stride)),
+                None => self.size_of(Self::id_operand(inst, 0)?),
+            },
+            Op::TypeStruct => {
+                let last = inst.operands.len().checked_sub(1)?;
+                self.runtime_array_stride(Self::id_operand(inst, last)?)
+            }
+            _ => None,
+        }
+    }
+
+    /// The size in bytes of `ty`, or `None` if it isn't statically sized. Structs without member
+    /// offsets, as in workgroup memory, are counted as their members packed back to back.
+    fn size_of(&self, ty: Word) -> Option<u64> {
+        let inst = self.def(ty)?;
+        match inst.class.opcode {
+            Op::TypeBool => Some(4),
+            Op::TypeInt | Op::TypeFloat => Some(u64::from(Self::literal_operand(inst, 0)?) / 8),
+            Op::TypeVector | Op::TypeMatrix => {
+                let count = u64::from(Self::literal_operand(inst, 1)?);
+                Some(self.size_of(Self::id_operand(inst, 0)?)? * count)
+            }
+            Op::TypeArray => {
+                let length = Self::literal_operand(self.def(Self::id_operand(inst, 1)?)?, 0)?;
+                let stride = match self.decoration(ty, Decoration::ArrayStride) {
+                    Some(stride) => u64::from(stride),
+                    None => self.size_of(Self::id_operand(inst, 0)?)?,
+                };
+                Some(stride * u64::from(length))
+            }
+            Op::TypeStruct => {
+                let last = inst.operands.len().checked_sub(1)?;
+                let member_ty = Self::id_operand(inst, last)?;
+                match self.member_offsets.get(&(ty, last as u32)) {
+                    Some(&offset) => Some(u64::from(offset) + self.size_of(member_ty)?),
+                    None => (0..inst.operands.len())
+                        .map(|member| self.size_of(Self::id_operand(inst, member)?))
+                        .sum(),
+                }
+            }
+            _ => None,
+        }
+    }
+}
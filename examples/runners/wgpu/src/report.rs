@@ -0,0 +1,216 @@
+//! `--report`: builds every shader crate and summarizes what each of them asks of the GPU, and
+//! `--describe`: the same for a single shader or `--spv-file`.
+
+use crate::reflect::Reflection;
+use crate::{read_spirv, shader_builder, Options, RustGPUShader};
+use serde::Serialize;
+use strum::IntoEnumIterator;
+
+#[derive(Serialize)]
+struct ShaderReport {
+    shader: String,
+    entry_points: Vec<EntryPointReport>,
+    capabilities: Vec<String>,
+    bindings: Vec<BindingReport>,
+    /// The size in bytes of the push constant range, if the shader has push constants.
+    push_constant_size: Option<u64>,
+    workgroup_storage: u64,
+}
+
+#[derive(Serialize)]
+struct EntryPointReport {
+    name: String,
+    execution_model: String,
+    local_size: Option<[u32; 3]>,
+    interface: Vec<InterfaceReport>,
+}
+
+/// An input or output of an entry point at a `Location`.
+#[derive(Serialize)]
+struct InterfaceReport {
+    location: u32,
+    /// `Input` or `Output`.
+    storage_class: String,
+    /// The component type and count, e.g. `Float x4`, if it's a 32-bit scalar or vector.
+    format: Option<String>,
+    interpolation: String,
+}
+
+#[derive(Serialize)]
+struct BindingReport {
+    set: u32,
+    binding: u32,
+    kind: String,
+    /// `Uniform`, `ReadOnly` or `ReadWrite` for buffers.
+    access: Option<String>,
+    storage_class: String,
+    size: Option<u64>,
+    runtime_array: bool,
+}
+
+impl ShaderReport {
+    fn new(shader: String, reflection: &Reflection) -> Self {
+        Self {
+            shader,
+            entry_points: reflection
+                .entry_points
+                .iter()
+                .map(|entry_point| EntryPointReport {
+                    name: entry_point.name.clone(),
+                    execution_model: format!("{:?}", entry_point.execution_model),
+                    local_size: entry_point.local_size,
+                    interface: entry_point
+                        .interface
+                        .iter()
+                        .map(|var| InterfaceReport {
+                            location: var.location,
+                            storage_class: format!("{:?}", var.storage_class),
+                            format: var
+                                .format
+                                .map(|(component, count)| format!("{:?} x{}", component, count)),
+                            interpolation: format!("{:?}", var.interpolation),
+                        })
+                        .collect(),
+                })
+                .collect(),
+            capabilities: reflection
+                .capabilities
+                .iter()
+                .map(|capability| format!("{:?}", capability))
+                .collect(),
+            bindings: reflection
+                .bindings
+                .iter()
+                .map(|binding| BindingReport {
+                    set: binding.set,
+                    binding: binding.binding,
+                    kind: format!("{:?}", binding.kind),
+                    access: binding.access.map(|access| format!("{:?}", access)),
+                    storage_class: format!("{:?}", binding.storage_class),
+                    size: binding.size,
+                    runtime_array: binding.runtime_array,
+                })
+                .collect(),
+            push_constant_size: reflection.push_constant_size,
+            workgroup_storage: reflection.workgroup_storage,
+        }
+    }
+
+    fn print(&self) {
+        println!("{}", self.shader);
+        println!("  capabilities: {}", self.capabilities.join(", "));
+        for entry_point in &self.entry_points {
+            match entry_point.local_size {
+                Some(local_size) => println!(
+                    "  entry point {} ({}, local size {:?})",
+                    entry_point.name, entry_point.execution_model, local_size
+                ),
+                None => println!(
+                    "  entry point {} ({})",
+                    entry_point.name, entry_point.execution_model
+                ),
+            }
+            for var in &entry_point.interface {
+                let format = var.format.as_deref().unwrap_or("unknown format");
+                // Smooth is the default, and all that non-fragment interfaces can be.
+                if var.interpolation == "Smooth" {
+                    println!("    {} {}: {}", var.storage_class, var.location, format);
+                } else {
+                    println!(
+                        "    {} {}: {} ({})",
+                        var.storage_class, var.location, format, var.interpolation
+                    );
+                }
+            }
+        }
+        for binding in &self.bindings {
+            let size = match (binding.size, binding.runtime_array) {
+                (Some(size), true) => format!("{} bytes + runtime array", size),
+                (Some(size), false) => format!("{} bytes", size),
+                (None, _) => "unsized".to_string(),
+            };
+            let kind = match &binding.access {
+                Some(access) => format!("{} ({})", binding.kind, access),
+                None => binding.kind.clone(),
+            };
+            println!(
+                "  binding {}.{}: {} in {} ({})",
+                binding.set, binding.binding, kind, binding.storage_class, size
+            );
+        }
+        if let Some(size) = self.push_constant_size {
+            println!("  push constants: {} bytes", size);
+        }
+        if self.workgroup_storage > 0 {
+            println!("  workgroup storage: {} bytes", self.workgroup_storage);
+        }
+    }
+}
+
+/// Builds every shader crate and prints a report on each, as JSON with `--json`. Crates that fail
+/// to build are left out, the compiler having already said why.
+pub fn print(options: &Options) {
+    let reports = RustGPUShader::iter()
+        .filter_map(|shader| {
+            let compile_result = shader_builder(options, shader).build().ok()?;
+            let module_path = compile_result.module.unwrap_single();
+            let words = match read_spirv(module_path) {
+                Ok(words) => words,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    return None;
+                }
+            };
+            match Reflection::new(&words) {
+                Ok(reflection) => Some(ShaderReport::new(shader.to_string(), &reflection)),
+                Err(err) => {
+                    eprintln!("{} is not valid SPIR-V: {:?}", shader, err);
+                    None
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&reports).unwrap());
+    } else {
+        for report in &reports {
+            report.print();
+        }
+    }
+}
+
+/// Builds `--shader` (or loads the `--spv-file`) and prints its interface like a `--report`
+/// entry, as JSON with `--json`. Returns whether the module could be described.
+pub fn describe(options: &Options) -> bool {
+    let (name, words) = match &options.spv_file {
+        Some(path) => (path.display().to_string(), read_spirv(path)),
+        None => match shader_builder(options, options.shader).build() {
+            Ok(compile_result) => (
+                options.shader.to_string(),
+                read_spirv(compile_result.module.unwrap_single()),
+            ),
+            // The compiler has already said why.
+            Err(_) => return false,
+        },
+    };
+    let words = match words {
+        Ok(words) => words,
+        Err(err) => {
+            eprintln!("{}", err);
+            return false;
+        }
+    };
+    let report = match Reflection::new(&words) {
+        Ok(reflection) => ShaderReport::new(name, &reflection),
+        Err(err) => {
+            eprintln!("{} is not valid SPIR-V: {:?}", name, err);
+            return false;
+        }
+    };
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    } else {
+        report.print();
+    }
+    true
+}
@@ -0,0 +1,150 @@
+//! [`run_compute!`](crate::run_compute): builds a shader crate and runs one of its compute entry
+//! points over an input, for quick experiments that don't need the rest of the runner.
+
+use crate::{ComputeContext, Options, RunnerError, SpirvModule};
+use futures::executor::block_on;
+use std::path::Path;
+
+/// An element of the storage buffer a compute entry point runs over. The buffer is uploaded and
+/// read back as 32-bit words, with narrower elements packed several to a word, see
+/// [`pack_elements`].
+pub trait ComputeElement: Copy {
+    /// How many elements are packed into each 32-bit word.
+    const PER_WORD: usize = 1;
+
+    /// The bits of the element, in the low `32 / PER_WORD` bits of the word.
+    fn to_word(self) -> u32;
+    fn from_word(word: u32) -> Self;
+}
+
+impl ComputeElement for u32 {
+    fn to_word(self) -> u32 {
+        self
+    }
+
+    fn from_word(word: u32) -> Self {
+        word
+    }
+}
+
+impl ComputeElement for i32 {
+    fn to_word(self) -> u32 {
+        self as u32
+    }
+
+    fn from_word(word: u32) -> Self {
+        word as i32
+    }
+}
+
+impl ComputeElement for f32 {
+    fn to_word(self) -> u32 {
+        self.to_bits()
+    }
+
+    fn from_word(word: u32) -> Self {
+        f32::from_bits(word)
+    }
+}
+
+impl ComputeElement for u8 {
+    const PER_WORD: usize = 4;
+
+    fn to_word(self) -> u32 {
+        u32::from(self)
+    }
+
+    fn from_word(word: u32) -> Self {
+        word as u8
+    }
+}
+
+impl ComputeElement for i8 {
+    const PER_WORD: usize = 4;
+
+    fn to_word(self) -> u32 {
+        u32::from(self as u8)
+    }
+
+    fn from_word(word: u32) -> Self {
+        word as i8
+    }
+}
+
+/// Packs `elements` into 32-bit words, [`ComputeElement::PER_WORD`] to a word, the first in the
+/// lowest bits. The last word is padded with zeros.
+///
+/// This is the layout of a `&[u8]` in a little-endian buffer, so a shader reads byte `i` of the
+/// input as `(input[i / 4] >> (8 * (i % 4))) & 0xff`. That's also how shaders have to read it:
+/// binding the buffer as a `&[u8]` needs the `StorageBuffer8BitAccess` capability, see
+/// [`RunnerError::UnsupportedCapability`](crate::RunnerError::UnsupportedCapability). `Int8`
+/// arithmetic on the unpacked bytes is fine.
+pub fn pack_elements<T: ComputeElement>(elements: &[T]) -> Vec<u32> {
+    let bits = 32 / T::PER_WORD;
+    // Masked, so that a sign extended element doesn't spill into the next one.
+    let mask = u32::MAX >> (32 - bits);
+    elements
+        .chunks(T::PER_WORD)
+        .map(|chunk| {
+            chunk.iter().enumerate().fold(0, |word, (i, &element)| {
+                word | (element.to_word() & mask) << (bits * i)
+            })
+        })
+        .collect()
+}
+
+/// The first `len` elements packed into `words` by [`pack_elements`].
+pub fn unpack_elements<T: ComputeElement>(words: &[u32], len: usize) -> Vec<T> {
+    let bits = 32 / T::PER_WORD;
+    words
+        .iter()
+        .flat_map(|&word| (0..T::PER_WORD).map(move |i| T::from_word(word >> (bits * i))))
+        .take(len)
+        .collect()
+}
+
+/// Builds the shader crate in `path`, dispatches `entry_point` over `input` in binding 0 on a
+/// throwaway device, and returns the contents of binding 0 afterwards. This is what
+/// [`run_compute!`](crate::run_compute) expands to.
+///
+/// Bytes are [packed](pack_elements) four to a word, with one invocation per word rather than
+/// per byte.
+pub fn run_compute_crate<T: ComputeElement>(
+    path: impl AsRef<Path>,
+    entry_point: &str,
+    input: &[T],
+) -> Result<Vec<T>, RunnerError> {
+    let options = Options {
+        quiet: true,
+        entry_point: entry_point.to_string(),
+        ..Options::default()
+    };
+    let compile_result = crate::crate_builder(&options, path)
+        .build()
+        .map_err(|_| RunnerError::BuildFailed)?;
+    let module = SpirvModule::read(compile_result.module.unwrap_single())?;
+    let src = pack_elements(input);
+    let output = block_on(async {
+        let context = ComputeContext::new(&options).await?;
+        context.dispatch(&options, &module.descriptor(), &src).await
+    })?;
+    Ok(unpack_elements(&output.result, input.len()))
+}
+
+/// Builds a shader crate and runs one of its compute entry points over an input, returning the
+/// results as the input's element type:
+///
+/// ```no_run
+/// let squares: Vec<f32> =
+///     example_runner_wgpu::run_compute!("shaders/square", "main_cs", [1.0f32, 2.0, 3.0])?;
+/// # Ok::<(), example_runner_wgpu::RunnerError>(())
+/// ```
+///
+/// The input is bound as a storage buffer at binding 0, and overwritten by the results. See
+/// [`run_compute_crate`] for the details.
+#[macro_export]
+macro_rules! run_compute {
+    ($path:expr, $entry_point:expr, $input:expr $(,)?) => {
+        $crate::run_compute_crate($path, $entry_point, &$input[..])
+    };
+}
@@ -0,0 +1,85 @@
+//! The `smoke` binary: builds and runs every example shader once, reporting which of them work.
+
+use crate::reflect::Reflection;
+use crate::{
+    is_compute_shader, maybe_watch, ComputeContext, OptionsBuilder, RunnerError, RustGPUShader,
+    Verbosity,
+};
+use futures::executor::block_on;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use strum::IntoEnumIterator;
+
+/// The inputs the compute shader is dispatched over, fewer than usual to keep the run short.
+const SMOKE_SRC_LEN: u32 = 1024;
+
+enum Outcome {
+    Passed,
+    /// Built and reflected, but not run, for want of a GPU.
+    Skipped,
+    Failed(String),
+}
+
+/// Builds every example shader and runs it once: compute shaders are dispatched, and graphics
+/// shaders render a frame offscreen. Without an adapter, shaders are only built and reflected, as
+/// with `--dry-run`. Returns whether none of them failed.
+pub fn run() -> bool {
+    let mut failures = 0;
+    for shader in RustGPUShader::iter() {
+        // wgpu panics on validation errors, which should fail the shader rather than the run.
+        let outcome =
+            catch_unwind(AssertUnwindSafe(|| smoke_test(shader))).unwrap_or_else(|panic| {
+                let message = panic
+                    .downcast_ref::<String>()
+                    .map(String::as_str)
+                    .or_else(|| panic.downcast_ref::<&str>().copied())
+                    .unwrap_or("panicked");
+                Outcome::Failed(message.to_string())
+            });
+        match outcome {
+            Outcome::Passed => println!("{}: passed", shader),
+            Outcome::Skipped => println!("{}: skipped running it, there's no adapter", shader),
+            Outcome::Failed(reason) => {
+                println!("{}: FAILED: {}", shader, reason);
+                failures += 1;
+            }
+        }
+    }
+    failures == 0
+}
+
+fn smoke_test(shader: RustGPUShader) -> Outcome {
+    let options = match OptionsBuilder::new()
+        .shader(shader)
+        .verbosity(Verbosity::Quiet)
+        .build()
+    {
+        Ok(options) => options,
+        Err(err) => return Outcome::Failed(err.to_string()),
+    };
+    let shader_binary = match maybe_watch(&options, true).recv() {
+        Ok(shader_binary) => shader_binary,
+        // The compiler has already said why.
+        Err(_) => return Outcome::Failed("the build failed".to_string()),
+    };
+    if let wgpu::ShaderSource::SpirV(spirv) = &shader_binary.source {
+        if let Err(err) = Reflection::new(spirv) {
+            return Outcome::Failed(format!("the module is not valid SPIR-V: {:?}", err));
+        }
+    }
+
+    let result = if is_compute_shader(shader) {
+        block_on(async {
+            let context = ComputeContext::new(&options).await?;
+            let src = (1..=SMOKE_SRC_LEN).collect::<Vec<_>>();
+            context.dispatch(&options, &shader_binary, &src).await
+        })
+        .map(|_| ())
+    } else {
+        block_on(crate::graphics::render_offscreen(&options, shader_binary))
+    };
+    match result {
+        Ok(()) => Outcome::Passed,
+        Err(RunnerError::NoAdapter) => Outcome::Skipped,
+        Err(err) => Outcome::Failed(err.to_string()),
+    }
+}
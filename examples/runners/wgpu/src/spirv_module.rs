@@ -0,0 +1,90 @@
+//! [`SpirvModule`]: a SPIR-V module that owns its words, for callers that keep modules around.
+
+use crate::RunnerError;
+use std::borrow::Cow;
+use std::path::Path;
+
+/// A SPIR-V module, owning its words and lending out a [`wgpu::ShaderModuleDescriptor`] that
+/// borrows them whenever a shader module is to be created.
+///
+/// A descriptor owning its words through a `Cow` can't be reused without either cloning them or
+/// tying every user to its `'static` lifetime, which gets in the way of caching compiled modules
+/// and dispatching them over and over, e.g. with [`ComputeContext::dispatch`]:
+///
+/// ```ignore
+/// let module = SpirvModule::read(path)?;
+/// for src in inputs {
+///     context.dispatch(&options, &module.descriptor(), src).await?;
+/// }
+/// ```
+///
+/// [`ComputeContext::dispatch`]: crate::ComputeContext::dispatch
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpirvModule {
+    label: Option<String>,
+    words: Vec<u32>,
+    flags: wgpu::ShaderFlags,
+}
+
+impl SpirvModule {
+    /// The module made of `words`, validated by wgpu as usual.
+    pub fn new(words: Vec<u32>) -> Self {
+        Self {
+            label: None,
+            words,
+            flags: wgpu::ShaderFlags::default(),
+        }
+    }
+
+    /// Reads the module at `path`, in whichever endianness it was written.
+    pub fn read(path: impl AsRef<Path>) -> Result<Self, RunnerError> {
+        crate::read_spirv(path.as_ref())
+            .map(Self::new)
+            .map_err(RunnerError::SpvLoadFailed)
+    }
+
+    /// Labels the shader modules created from this one, for wgpu's errors and debuggers.
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    /// Sets the flags the shader modules are created with, e.g. to skip validating them.
+    pub fn flags(mut self, flags: wgpu::ShaderFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    pub fn words(&self) -> &[u32] {
+        &self.words
+    }
+
+    pub fn into_words(self) -> Vec<u32> {
+        self.words
+    }
+
+    /// A descriptor borrowing the module, to create a shader module from.
+    pub fn descriptor(&self) -> wgpu::ShaderModuleDescriptor<'_> {
+        wgpu::ShaderModuleDescriptor {
+            label: self.label.as_deref(),
+            source: wgpu::ShaderSource::SpirV(Cow::Borrowed(&self.words)),
+            flags: self.flags,
+        }
+    }
+
+    /// A descriptor owning the module, for the parts of the runner that still pass descriptors
+    /// around rather than modules. Its label is dropped, as a `'static` one would have to leak.
+    pub fn into_descriptor(self) -> wgpu::ShaderModuleDescriptor<'static> {
+        wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::SpirV(Cow::Owned(self.words)),
+            flags: self.flags,
+        }
+    }
+}
+
+impl From<Vec<u32>> for SpirvModule {
+    fn from(words: Vec<u32>) -> Self {
+        Self::new(words)
+    }
+}
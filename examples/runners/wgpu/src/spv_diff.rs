@@ -0,0 +1,299 @@
+//! The `spv-diff` binary: disassembles two SPIR-V modules and prints how they differ, e.g. to
+//! review what a codegen or optimizer change did to a shader.
+
+use crate::read_spirv;
+use spirv_tools::assembler::{self, Assembler, DisassembleOptions};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::Path;
+
+/// The number of unchanged lines shown around each change.
+const CONTEXT: usize = 2;
+
+/// Diffs of more lines than this (after trimming the common start and end) are shown as the old
+/// lines all replaced by the new ones, rather than spending quadratic time and memory on them.
+const MAX_DIFF_CELLS: usize = 1 << 26;
+
+/// Disassembles `a` and `b` with spirv-tools, and returns a line diff of the two, or `None` if
+/// they disassemble the same.
+///
+/// Ids are named after their debug names and types where spirv-tools can, and the rest are
+/// renumbered in order of first appearance in each module, so that ids merely shifting by a
+/// change elsewhere doesn't make every line differ.
+pub fn diff_spirv(a: &[u32], b: &[u32]) -> Result<Option<String>, String> {
+    let a = disassemble(a)?;
+    let b = disassemble(b)?;
+    let a = a.lines().collect::<Vec<_>>();
+    let b = b.lines().collect::<Vec<_>>();
+    if a == b {
+        return Ok(None);
+    }
+    Ok(Some(format_hunks(&a, &b, &diff_lines(&a, &b))))
+}
+
+fn disassemble(words: &[u32]) -> Result<String, String> {
+    let options = DisassembleOptions {
+        no_header: true,
+        use_friendly_names: true,
+        ..DisassembleOptions::default()
+    };
+    let text = assembler::create(None)
+        .disassemble(words, options)
+        .map_err(|err| err.to_string())?
+        .unwrap_or_default();
+    Ok(renumber_ids(&text))
+}
+
+/// Replaces every numeric id (`%123`) in `text` with one numbered by order of first appearance.
+fn renumber_ids(text: &str) -> String {
+    let mut ids = HashMap::new();
+    let mut renumbered = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('%') {
+        renumbered.push_str(&rest[..=start]);
+        rest = &rest[start + 1..];
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        // Friendly names such as `%uint` aren't renumbered, nor are `%`s in string literals.
+        let is_numeric =
+            digits > 0 && !rest[digits..].starts_with(|c: char| c.is_alphanumeric() || c == '_');
+        if is_numeric {
+            let next = ids.len();
+            let id = *ids.entry(&rest[..digits]).or_insert(next);
+            write!(renumbered, "{}", id).unwrap();
+            rest = &rest[digits..];
+        }
+    }
+    renumbered.push_str(rest);
+    renumbered
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Line {
+    Same(usize, usize),
+    Removed(usize),
+    Added(usize),
+}
+
+/// The lines of `a` and `b` as kept, removed or added, in order, following their longest common
+/// subsequence.
+fn diff_lines(a: &[&str], b: &[&str]) -> Vec<Line> {
+    let prefix = a.iter().zip(b).take_while(|(a, b)| a == b).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (middle_a, middle_b) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    let mut lines = (0..prefix).map(|i| Line::Same(i, i)).collect::<Vec<_>>();
+    if middle_a.len() * middle_b.len() > MAX_DIFF_CELLS {
+        lines.extend((0..middle_a.len()).map(|i| Line::Removed(prefix + i)));
+        lines.extend((0..middle_b.len()).map(|j| Line::Added(prefix + j)));
+    } else {
+        // `lcs[i][j]` is the length of the longest common subsequence of `a[i..]` and `b[j..]`.
+        let width = middle_b.len() + 1;
+        let mut lcs = vec![0; (middle_a.len() + 1) * width];
+        for i in (0..middle_a.len()).rev() {
+            for j in (0..middle_b.len()).rev() {
+                lcs[i * width + j] = if middle_a[i] == middle_b[j] {
+                    lcs[(i + 1) * width + j + 1] + 1
+                } else {
+                    lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < middle_a.len() || j < middle_b.len() {
+            if i < middle_a.len() && j < middle_b.len() && middle_a[i] == middle_b[j] {
+                lines.push(Line::Same(prefix + i, prefix + j));
+                i += 1;
+                j += 1;
+            } else if j < middle_b.len()
+                && (i == middle_a.len() || lcs[i * width + j + 1] >= lcs[(i + 1) * width + j])
+            {
+                lines.push(Line::Added(prefix + j));
+                j += 1;
+            } else {
+                lines.push(Line::Removed(prefix + i));
+                i += 1;
+            }
+        }
+    }
+    let (end_a, end_b) = (a.len() - suffix, b.len() - suffix);
+    lines.extend((0..suffix).map(|k| Line::Same(end_a + k, end_b + k)));
+    lines
+}
+
+/// Formats the changed `lines` as hunks with [`CONTEXT`] unchanged lines around them, each
+/// headed by the line numbers it starts at in `a` and `b`.
+fn format_hunks(a: &[&str], b: &[&str], lines: &[Line]) -> String {
+    let changed = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Line::Same(..)))
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for &index in &changed {
+        let start = index.saturating_sub(CONTEXT);
+        let end = (index + CONTEXT + 1).min(lines.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+    let mut out = String::new();
+    for (start, end) in hunks {
+        // The first line of the hunk in each module, counting from 1.
+        let first_a = lines[start..]
+            .iter()
+            .find_map(|line| match *line {
+                Line::Same(i, _) | Line::Removed(i) => Some(i + 1),
+                Line::Added(_) => None,
+            })
+            .unwrap_or(a.len());
+        let first_b = lines[start..]
+            .iter()
+            .find_map(|line| match *line {
+                Line::Same(_, j) | Line::Added(j) => Some(j + 1),
+                Line::Removed(_) => None,
+            })
+            .unwrap_or(b.len());
+        writeln!(out, "@@ -{} +{} @@", first_a, first_b).unwrap();
+        for line in &lines[start..end] {
+            let (marker, text) = match *line {
+                Line::Same(i, _) => (' ', a[i]),
+                Line::Removed(i) => ('-', a[i]),
+                Line::Added(j) => ('+', b[j]),
+            };
+            writeln!(out, "{} {}", marker, text).unwrap();
+        }
+    }
+    out
+}
+
+/// Runs `spv-diff a.spv b.spv`, returning the exit code, which like `diff`'s is 0 if the modules
+/// are the same, 1 if they differ, and 2 if they couldn't be compared.
+pub fn run() -> i32 {
+    let args = std::env::args_os().skip(1).collect::<Vec<_>>();
+    let (a, b) = match args.as_slice() {
+        [a, b] => (Path::new(a), Path::new(b)),
+        _ => {
+            eprintln!("Usage: spv-diff <a.spv> <b.spv>");
+            return 2;
+        }
+    };
+    let diff = read_spirv(a)
+        .and_then(|a| Ok((a, read_spirv(b)?)))
+        .and_then(|(a, b)| diff_spirv(&a, &b));
+    match diff {
+        Ok(None) => 0,
+        Ok(Some(diff)) => {
+            print!("{}", diff);
+            1
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            2
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn diff(a: &[&str], b: &[&str]) -> String {
+        format_hunks(a, b, &diff_lines(a, b))
+    }
+
+    /// A module of just the given capabilities and a logical GLSL450 memory model.
+    fn module(capabilities: &[u32]) -> Vec<u32> {
+        let mut words = vec![0x0723_0203, 0x0001_0000, 0, 1, 0];
+        for &capability in capabilities {
+            words.extend(&[2 << 16 | 17, capability]);
+        }
+        words.extend(&[3 << 16 | 14, 0, 1]);
+        words
+    }
+
+    #[test]
+    fn renumbers_ids() {
+        assert_eq!(
+            renumber_ids("%7 = OpTypePointer Function %uint\n%9 = OpLoad %uint %7\n"),
+            "%0 = OpTypePointer Function %uint\n%1 = OpLoad %uint %0\n"
+        );
+        // Only whole ids, not names that happen to start with digits, nor a lone `%`.
+        assert_eq!(
+            renumber_ids("%3x %3_y OpString \"%\""),
+            "%3x %3_y OpString \"%\""
+        );
+    }
+
+    #[test]
+    fn same_lines() {
+        assert_eq!(diff(&["a", "b"], &["a", "b"]), "");
+    }
+
+    #[test]
+    fn separate_hunks() {
+        let a = ["a", "b", "c", "d", "e", "f", "g", "h", "i"];
+        let b = ["a", "c", "d", "e", "f", "g", "h", "X", "i"];
+        assert_eq!(
+            diff(&a, &b),
+            r#"@@ -1 +1 @@
+  a
+- b
+  c
+  d
+@@ -7 +6 @@
+  g
+  h
++ X
+  i
+"#
+        );
+    }
+
+    #[test]
+    fn nearby_changes_share_a_hunk() {
+        let a = ["a", "b", "c", "d", "e", "f"];
+        let b = ["a", "c", "d", "e", "X", "f"];
+        assert_eq!(diff(&a, &b).matches("@@ -").count(), 1);
+    }
+
+    #[test]
+    fn too_large_diffs_replace_everything() {
+        let a = vec!["a"; 1 << 13];
+        let b = vec!["b"; 1 << 14];
+        let lines = diff_lines(&a, &b);
+        assert_eq!(lines.len(), a.len() + b.len());
+        assert!(lines[..a.len()]
+            .iter()
+            .all(|line| matches!(line, Line::Removed(_))));
+        assert!(lines[a.len()..]
+            .iter()
+            .all(|line| matches!(line, Line::Added(_))));
+    }
+
+    #[test]
+    fn diff_modules() {
+        let shader = module(&[1]);
+        assert_eq!(diff_spirv(&shader, &shader), Ok(None));
+        assert_eq!(
+            diff_spirv(&shader, &module(&[1, 11])),
+            Ok(Some(
+                r#"@@ -1 +1 @@
+  OpCapability Shader
++ OpCapability Int64
+  OpMemoryModel Logical GLSL450
+"#
+                .to_string()
+            ))
+        );
+        assert!(diff_spirv(&shader, &[0xdead_beef]).is_err());
+    }
+}
@@ -0,0 +1,142 @@
+use std::error::Error;
+use std::fs::File;
+use std::num::NonZeroU32;
+use std::path::Path;
+
+/// Reads the 8-bit RGBA PNG at `path`, returning its width, height and pixels.
+pub fn read_png(path: &Path) -> Result<(u32, u32, Vec<u8>), Box<dyn Error>> {
+    let decoder = png::Decoder::new(File::open(path)?);
+    let (info, mut reader) = decoder.read_info()?;
+    if info.color_type != png::ColorType::RGBA || info.bit_depth != png::BitDepth::Eight {
+        return Err(format!(
+            "expected an 8-bit RGBA image, but it's {:?} {:?}",
+            info.bit_depth, info.color_type
+        )
+        .into());
+    }
+    let mut rgba = vec![0; info.buffer_size()];
+    reader.next_frame(&mut rgba)?;
+    Ok((info.width, info.height, rgba))
+}
+
+/// A sampled texture together with the sampler used to read it, for shaders that do filtered
+/// reads rather than accessing storage directly.
+pub struct SampledTexture {
+    view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+}
+
+impl SampledTexture {
+    /// Uploads `rgba`, `width` x `height` RGBA8 pixels, into a new texture.
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        width: u32,
+        height: u32,
+        rgba: &[u8],
+        filter: wgpu::FilterMode,
+    ) -> Self {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Sampled texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
+        });
+        queue.write_texture(
+            wgpu::TextureCopyView {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            rgba,
+            wgpu::TextureDataLayout {
+                offset: 0,
+                bytes_per_row: 4 * width,
+                rows_per_image: height,
+            },
+            size,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Sampler"),
+            mag_filter: filter,
+            min_filter: filter,
+            ..Default::default()
+        });
+        Self { view, sampler }
+    }
+
+    /// Loads an 8-bit RGBA PNG into a new texture.
+    pub fn from_png(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: &Path,
+        filter: wgpu::FilterMode,
+    ) -> Result<Self, Box<dyn Error>> {
+        let (width, height, rgba) = read_png(path)?;
+        Ok(Self::new(device, queue, width, height, &rgba, filter))
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    /// Layout entries for a sampler and a texture, or an array of `count` textures.
+    pub fn layout_entries(
+        sampler_binding: u32,
+        texture_binding: u32,
+        count: Option<NonZeroU32>,
+    ) -> [wgpu::BindGroupLayoutEntry; 2] {
+        [
+            wgpu::BindGroupLayoutEntry {
+                binding: sampler_binding,
+                count: None,
+                visibility: wgpu::ShaderStage::COMPUTE,
+                ty: wgpu::BindingType::Sampler {
+                    filtering: true,
+                    comparison: false,
+                },
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: texture_binding,
+                count,
+                visibility: wgpu::ShaderStage::COMPUTE,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+            },
+        ]
+    }
+
+    /// Bind group entries for this texture's sampler, and either this texture or all of `array`.
+    pub fn bind_group_entries<'a>(
+        &'a self,
+        sampler_binding: u32,
+        texture_binding: u32,
+        array: Option<&'a [&'a wgpu::TextureView]>,
+    ) -> [wgpu::BindGroupEntry<'a>; 2] {
+        [
+            wgpu::BindGroupEntry {
+                binding: sampler_binding,
+                resource: wgpu::BindingResource::Sampler(&self.sampler),
+            },
+            wgpu::BindGroupEntry {
+                binding: texture_binding,
+                resource: match array {
+                    Some(views) => wgpu::BindingResource::TextureViewArray(views),
+                    None => wgpu::BindingResource::TextureView(&self.view),
+                },
+            },
+        ]
+    }
+}
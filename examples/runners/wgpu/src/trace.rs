@@ -0,0 +1,131 @@
+//! `--trace`: a timeline of a compute run, written in the Chrome trace event format that
+//! `chrome://tracing` and Perfetto load.
+
+use crate::Options;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The track of the CPU side of the run: building, setup, encoding and readback.
+const CPU_TRACK: u32 = 1;
+/// The track of the GPU time of each timed dispatch.
+const GPU_TRACK: u32 = 2;
+
+/// A single event, see the "Trace Event Format" document linked from `chrome://tracing`.
+#[derive(Serialize)]
+struct Event {
+    name: String,
+    /// `X` for a complete (duration) event, `M` for metadata naming a track.
+    ph: &'static str,
+    /// Microseconds since the start of the trace.
+    ts: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dur: Option<f64>,
+    pid: u32,
+    tid: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    args: Option<serde_json::Value>,
+}
+
+/// Collects the duration events of a run, to be written to the `--trace` file with
+/// [`Trace::write`].
+///
+/// Without `--trace` nothing is recorded, and the clock is never read, which matters on the web
+/// where `Instant::now` panics.
+pub struct Trace {
+    path: Option<PathBuf>,
+    origin: Option<Instant>,
+    events: Mutex<Vec<Event>>,
+}
+
+impl Trace {
+    pub fn new(options: &Options) -> Self {
+        Self {
+            path: options.trace.clone(),
+            origin: options.trace.as_ref().map(|_| Instant::now()),
+            events: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// The start of a span to [`Trace::end`], or `None` when not tracing.
+    pub fn start(&self) -> Option<Instant> {
+        self.origin.map(|_| Instant::now())
+    }
+
+    /// Records the CPU span from `start` until now as `name`.
+    pub fn end(&self, name: impl Into<String>, start: Option<Instant>) {
+        if let Some(start) = start {
+            self.record(name.into(), CPU_TRACK, start, start.elapsed());
+        }
+    }
+
+    /// Records `duration` of GPU work as `name`, starting at `start`.
+    ///
+    /// This version of wgpu can't relate GPU timestamps to the CPU's clock, so only the duration
+    /// of GPU work is known, and callers have to pick a plausible start for it.
+    pub fn gpu(&self, name: impl Into<String>, start: Option<Instant>, duration: Duration) {
+        if let Some(start) = start {
+            self.record(name.into(), GPU_TRACK, start, duration);
+        }
+    }
+
+    fn record(&self, name: String, tid: u32, start: Instant, duration: Duration) {
+        let origin = match self.origin {
+            Some(origin) => origin,
+            None => return,
+        };
+        let event = Event {
+            name,
+            ph: "X",
+            ts: micros(start.saturating_duration_since(origin)),
+            dur: Some(micros(duration)),
+            pid: 1,
+            tid,
+            args: None,
+        };
+        if let Ok(mut events) = self.events.lock() {
+            events.push(event);
+        }
+    }
+
+    /// Writes the events recorded so far to the `--trace` file, if there is one.
+    pub fn write(&self) {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return,
+        };
+        let events = match self.events.lock() {
+            Ok(events) => events,
+            Err(_) => return,
+        };
+        if let Err(err) = write_events(path, &events) {
+            eprintln!("Failed to write {}: {}", path.display(), err);
+        }
+    }
+}
+
+fn write_events(path: &Path, events: &[Event]) -> Result<(), Box<dyn std::error::Error>> {
+    let track_names = [(CPU_TRACK, "CPU"), (GPU_TRACK, "GPU")]
+        .iter()
+        .map(|&(tid, name)| Event {
+            name: "thread_name".to_string(),
+            ph: "M",
+            ts: 0.0,
+            dur: None,
+            pid: 1,
+            tid,
+            args: Some(serde_json::json!({ "name": name })),
+        })
+        .collect::<Vec<_>>();
+    let trace = serde_json::json!({
+        "traceEvents": track_names.iter().chain(events).collect::<Vec<_>>(),
+        "displayTimeUnit": "ms",
+    });
+    std::fs::write(path, serde_json::to_string(&trace)?)?;
+    Ok(())
+}
+
+fn micros(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1e6
+}
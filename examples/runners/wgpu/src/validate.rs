@@ -0,0 +1,61 @@
+//! `--validate-only`: builds the shader (or loads the `--spv-file`) and runs `spirv-val` over it,
+//! without touching the GPU.
+
+use crate::{read_spirv, shader_builder, Options, SHADER_TARGET};
+use spirv_tools::val::{self, Validator};
+use spirv_tools::TargetEnv;
+
+/// Validates the module `options` picks, printing the SPIR-V version and target environment it's
+/// validated for, and the validator's diagnostics. Returns whether the module is valid.
+///
+/// Building the shader already validates it as part of linking, so this mostly matters for
+/// `--spv-file`s, which may come from anywhere.
+pub fn run(options: &Options) -> bool {
+    let words = match &options.spv_file {
+        Some(path) => read_spirv(path),
+        None => match shader_builder(options, options.shader).build() {
+            Ok(compile_result) => read_spirv(compile_result.module.unwrap_single()),
+            // The compiler has already said why, which includes validation errors.
+            Err(_) => return false,
+        },
+    };
+    let words = match words {
+        Ok(words) => words,
+        Err(err) => {
+            eprintln!("{}", err);
+            return false;
+        }
+    };
+
+    let env = SHADER_TARGET.trim_start_matches("spirv-unknown-");
+    let target_env = match env.parse::<TargetEnv>() {
+        Ok(target_env) => target_env,
+        Err(_) => {
+            eprintln!("spirv-val doesn't know the target environment `{}`", env);
+            return false;
+        }
+    };
+    match spirv_version(&words) {
+        Some((major, minor)) => println!(
+            "Validating SPIR-V {}.{} for {}",
+            major, minor, SHADER_TARGET
+        ),
+        None => println!("Validating SPIR-V of unknown version for {}", SHADER_TARGET),
+    }
+    match val::create(Some(target_env)).validate(&words, None) {
+        Ok(()) => {
+            println!("The module is valid");
+            true
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            false
+        }
+    }
+}
+
+/// The major and minor version in the header of `words`.
+fn spirv_version(words: &[u32]) -> Option<(u32, u32)> {
+    let version = *words.get(1)?;
+    Some(((version >> 16) & 0xff, (version >> 8) & 0xff))
+}
@@ -1,6 +1,6 @@
 #![cfg_attr(
     target_arch = "spirv",
-    feature(register_attr),
+    feature(register_attr, asm),
     register_attr(spirv),
     no_std
 )]
@@ -13,6 +13,8 @@ use glam::UVec3;
 use spirv_std::glam;
 #[cfg(not(target_arch = "spirv"))]
 use spirv_std::macros::spirv;
+#[cfg(target_arch = "spirv")]
+use spirv_std::memory::{Scope, Semantics};
 
 // Adapted from the wgpu hello-compute example
 
@@ -46,3 +48,93 @@ pub fn main_cs(
     let index = id.x as usize;
     prime_indices[index] = collatz(prime_indices[index]).unwrap_or(u32::MAX);
 }
+
+/// The number of invocations in each workgroup of [`main_scan_cs`].
+const SCAN_WORKGROUP_SIZE: usize = 64;
+
+/// Waits for the whole workgroup, making its writes to workgroup memory visible.
+#[cfg(target_arch = "spirv")]
+fn workgroup_barrier() {
+    unsafe {
+        spirv_std::arch::control_barrier::<
+            { Scope::Workgroup as u32 },
+            { Scope::Workgroup as u32 },
+            { Semantics::WORKGROUP_MEMORY.bits() | Semantics::ACQUIRE_RELEASE.bits() },
+        >();
+    }
+}
+
+#[cfg(not(target_arch = "spirv"))]
+fn workgroup_barrier() {}
+
+/// Like [`main_cs`], but replaces each step count with the largest one up to it in its workgroup,
+/// with an inclusive prefix max scan. Carrying the maximum of each workgroup into the next gives
+/// the running maxima, whose increases are <https://oeis.org/A006877>.
+#[spirv(compute(threads(64)))]
+pub fn main_scan_cs(
+    #[spirv(global_invocation_id)] id: UVec3,
+    #[spirv(local_invocation_id)] local_id: UVec3,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] prime_indices: &mut [u32],
+    #[spirv(workgroup)] scan: &mut [u32; SCAN_WORKGROUP_SIZE],
+) {
+    let index = id.x as usize;
+    let local = local_id.x as usize;
+    scan[local] = collatz(prime_indices[index]).unwrap_or(u32::MAX);
+    // Hillis-Steele: after the step with a given offset, each element is the maximum of twice
+    // that many elements up to it.
+    let mut offset = 1;
+    while offset < SCAN_WORKGROUP_SIZE {
+        workgroup_barrier();
+        let preceding = if local >= offset {
+            scan[local - offset]
+        } else {
+            0
+        };
+        workgroup_barrier();
+        if preceding > scan[local] {
+            scan[local] = preceding;
+        }
+        offset *= 2;
+    }
+    prime_indices[index] = scan[local];
+}
+
+/// The number of steps from which [`main_count_cs`] counts an input.
+const COUNT_MIN_STEPS: u32 = 256;
+
+/// Adds one to `counter`, atomically across every invocation of the dispatch.
+#[cfg(target_arch = "spirv")]
+fn atomic_increment(counter: &mut u32) {
+    unsafe {
+        asm! {
+            "%u32 = OpTypeInt 32 0",
+            "%scope = OpConstant %u32 {scope}",
+            "%semantics = OpConstant %u32 {semantics}",
+            "%old = OpAtomicIIncrement %u32 {counter} %scope %semantics",
+            counter = in(reg) counter,
+            scope = const Scope::Device as u32,
+            semantics = const Semantics::NONE.bits(),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "spirv"))]
+fn atomic_increment(counter: &mut u32) {
+    *counter += 1;
+}
+
+/// Counts the inputs whose Collatz sequences take at least [`COUNT_MIN_STEPS`] steps, or overflow,
+/// into `count`, rather than writing a result per input. Only the count has to be read back, as
+/// with `--entry main_count_cs --count-only`, which makes this a template for reductions.
+#[spirv(compute(threads(64)))]
+pub fn main_count_cs(
+    #[spirv(global_invocation_id)] id: UVec3,
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 0)] prime_indices: &[u32],
+    #[spirv(storage_buffer, descriptor_set = 0, binding = 1)] count: &mut u32,
+) {
+    let input = prime_indices[id.x as usize];
+    // The padding after the inputs is 0, which has no sequence to count.
+    if input != 0 && collatz(input).map_or(true, |steps| steps >= COUNT_MIN_STEPS) {
+        atomic_increment(count);
+    }
+}
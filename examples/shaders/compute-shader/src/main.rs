@@ -3,15 +3,36 @@ use std::time::Instant;
 use compute_shader::collatz;
 use rayon::prelude::*;
 
+/// The number of threads to compute the reference results on, from `--cpu-threads N`. With 1, the
+/// results are computed on a single thread, for deterministic debugging; without it, rayon picks
+/// one thread per core.
+fn cpu_threads() -> usize {
+    let mut args = std::env::args().skip(1);
+    match (args.next().as_deref(), args.next()) {
+        (None, _) => 0,
+        (Some("--cpu-threads"), Some(threads)) => match threads.parse() {
+            Ok(threads) if threads > 0 => threads,
+            _ => panic!("--cpu-threads takes a number of threads, not `{}`", threads),
+        },
+        _ => panic!("Usage: compute-shader [--cpu-threads N]"),
+    }
+}
+
 fn main() {
     let top = 2u32.pow(20);
     let src_range = 1..top;
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(cpu_threads())
+        .build()
+        .expect("Failed to create the thread pool");
     let start = Instant::now();
-    let result = src_range
-        .clone()
-        .into_par_iter()
-        .map(collatz)
-        .collect::<Vec<_>>();
+    let result = pool.install(|| {
+        src_range
+            .clone()
+            .into_par_iter()
+            .map(collatz)
+            .collect::<Vec<_>>()
+    });
     let took = start.elapsed();
     let mut max = 0;
     for (src, out) in src_range.zip(result.iter().copied()) {
@@ -28,5 +49,5 @@ fn main() {
             }
         }
     }
-    println!("Took: {:?}", took);
+    println!("Took: {:?} on {} threads", took, pool.current_num_threads());
 }
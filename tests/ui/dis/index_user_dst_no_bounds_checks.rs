@@ -0,0 +1,14 @@
+// build-pass
+// compile-flags: -C llvm-args=--disassemble-entry=main
+// compile-flags: -C llvm-args=--no-bounds-checks
+
+// Like `index_user_dst`, but failing the bounds check is unreachable, rather than a panic that
+// aborts with an infinite loop.
+
+use spirv_std as _;
+
+#[spirv(fragment)]
+pub fn main(#[spirv(storage_buffer, descriptor_set = 0, binding = 0)] slice: &mut [f32]) {
+    let float: f32 = slice[0];
+    let _ = float;
+}
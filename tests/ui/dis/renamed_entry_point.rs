@@ -0,0 +1,11 @@
+// build-pass
+// compile-flags: -C llvm-args=--disassemble-globals
+// compile-flags: -C llvm-args=--entry-point-name=main=hello_world
+// normalize-stderr-test "OpCapability VulkanMemoryModel\n" -> ""
+// normalize-stderr-test "OpExtension .SPV_KHR_vulkan_memory_model.\n" -> ""
+// normalize-stderr-test "OpMemoryModel Logical Vulkan" -> "OpMemoryModel Logical Simple"
+
+use spirv_std as _;
+
+#[spirv(fragment)]
+pub fn main() {}